@@ -3,4 +3,8 @@ set -e
 
 RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release
 mkdir -p out
-cp target/wasm32-unknown-unknown/release/*.wasm out/staking-contract.wasm
\ No newline at end of file
+cp target/wasm32-unknown-unknown/release/*.wasm out/staking-contract.wasm
+
+# Mock FT used by simulation tests
+(cd token-test/mock-ft && RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release)
+cp token-test/mock-ft/target/wasm32-unknown-unknown/release/mock_ft.wasm token-test/mock-ft.wasm
@@ -3,41 +3,152 @@ use near_sdk::Timestamp;
 use crate::*;
 
 
+/**
+ * Default hold account of baseline layout moved by migrate, converted to current layout on read
+ */
 #[derive(BorshDeserialize, BorshSerialize)]
 pub enum UpgradableAccount {
-    Default(Account),
+    Default(AccountV1),
     Current(Account)
 }
 
 impl From<UpgradableAccount> for Account {
     fn from(account: UpgradableAccount) -> Self {
         match account {
-            UpgradableAccount::Default(account) => account,
+            UpgradableAccount::Default(account) => Account::from(account),
             UpgradableAccount::Current(account) => account
         }
     }
 }
 
+/**
+ * Account enum of baseline layout, baseline wrote both variant with same account layout, only used in migrate
+ */
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum OldUpgradableAccount {
+    Default(AccountV1),
+    Current(AccountV1)
+}
+
+impl From<OldUpgradableAccount> for AccountV1 {
+    fn from(account: OldUpgradableAccount) -> Self {
+        match account {
+            OldUpgradableAccount::Default(account) => account,
+            OldUpgradableAccount::Current(account) => account
+        }
+    }
+}
+
+/**
+ * Account layout of baseline contract
+ */
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Clone)]
+pub struct AccountV1 {
+    pub stake_balance: Balance,
+    pub pre_stake_balance: Balance,
+    pub pre_reward: Balance,
+    pub last_block_balance_change: BlockHeight,
+    pub unstake_balance: Balance,
+    pub unstake_start_timestamp: Timestamp,
+    pub unstake_available_epoch_height: EpochHeight
+}
+
+impl From<AccountV1> for Account {
+    fn from(account: AccountV1) -> Self {
+        Account {
+            stake_balance: account.stake_balance,
+            pre_reward: account.pre_reward,
+            last_block_balance_change: account.last_block_balance_change,
+            unstake_balance: account.unstake_balance,
+            unstake_start_timestamp: account.unstake_start_timestamp,
+            unstake_available_epoch_height: account.unstake_available_epoch_height,
+            lock_tier: None,
+            lock_until_epoch: 0,
+            locked_balance: 0,
+            frozen: false,
+            reward_remainder: 0,
+            last_harvest_block: 0,
+            unwrap_on_harvest: false,
+            referrer: None,
+            boost_balance: 0,
+            operation_in_flight: false,
+            compound_count: 0,
+            last_compound_block: 0,
+            vesting_balance: 0,
+            vested_balance: 0,
+            vesting_start_block: 0,
+            vesting_end_block: 0
+        }
+    }
+}
+
 impl From<Account> for UpgradableAccount {
     fn from(account: Account) -> Self {
         UpgradableAccount::Current(account)
     }
 }
 
-#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, PartialEq, Debug, Serialize, Deserialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Account {
     pub stake_balance: Balance,
-    pub pre_stake_balance: Balance,
     pub pre_reward: Balance,
     pub last_block_balance_change: BlockHeight,
     pub unstake_balance: Balance,
     pub unstake_start_timestamp: Timestamp,
-    pub unstake_available_epoch_height: EpochHeight
+    pub unstake_available_epoch_height: EpochHeight,
+    pub lock_tier: Option<u8>, // Lock tier chose when stake, boost reward
+    pub lock_until_epoch: EpochHeight, // Locked balance can't unstake before this epoch
+    pub locked_balance: Balance, // Part of stake balance deposit with lock tier
+    pub frozen: bool, // Frozen account don't accrue reward, can't harvest or unstake
+    pub reward_remainder: Balance, // Reward smaller than 1 unit carry to next settlement, scaled by reward_precision_multiplier
+    pub last_harvest_block: BlockHeight, // Block of last successful harvest, 0 if never harvest
+    pub unwrap_on_harvest: bool, // Receive harvest reward as native NEAR if reward token is wrap NEAR
+    pub referrer: Option<AccountId>, // Referrer receive referral bonus of this account reward
+    pub boost_balance: Balance, // Boost token deposited to raise reward weight
+    pub operation_in_flight: bool, // Harvest or withdraw transfer is pending, cleared in callback
+    pub compound_count: u64, // Number of compound restaking reward into stake
+    pub last_compound_block: BlockHeight, // Reward accrual point of last compound, 0 if never compound
+    pub vesting_balance: Balance, // Harvested reward not released yet, release linearly until vesting_end_block
+    pub vested_balance: Balance, // Released reward waiting for claim_vested
+    pub vesting_start_block: BlockHeight, // Block vesting_balance was last released
+    pub vesting_end_block: BlockHeight // Block all vesting_balance is released
+}
+
+impl Account {
+    /**
+     * Stake balance still locked at epoch
+     */
+    pub fn locked_stake(&self, epoch_height: EpochHeight) -> Balance {
+        if epoch_height < self.lock_until_epoch {
+            self.locked_balance
+        } else {
+            0
+        }
+    }
+
+    /**
+     * Move vesting balance released since vesting_start_block to vested_balance
+     * Release is linear to vesting_end_block, so partial release keep same end block
+     */
+    pub fn release_vesting(&mut self, current_block: BlockHeight) {
+        let released: Balance = if current_block >= self.vesting_end_block {
+            self.vesting_balance
+        } else {
+            let elapsed: BlockHeight = current_block.saturating_sub(self.vesting_start_block);
+            let duration: BlockHeight = self.vesting_end_block - self.vesting_start_block;
+            u256_to_balance(U256::from(self.vesting_balance) * U256::from(elapsed) / U256::from(duration))
+        };
+
+        self.vesting_balance -= released;
+        self.vested_balance += released;
+        self.vesting_start_block = current_block;
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
 pub struct AccountJson {
     pub account_id: AccountId,
     pub stake_balance: U128,
@@ -46,5 +157,70 @@ pub struct AccountJson {
     pub can_withdraw: bool,
     pub start_unstake_timestamp: Timestamp,
     pub unstake_available_epoch: EpochHeight,
-    pub current_epoch: EpochHeight
-}
\ No newline at end of file
+    pub current_epoch: EpochHeight,
+    pub lock_tier: Option<u8>,
+    pub lock_until_epoch: EpochHeight,
+    pub available_to_unstake: U128,
+    pub pool_share_basis_points: u32, // Share of account stake in pool, 0 if pool is empty
+    pub is_accrual_capped: bool // Reward reached max_accrued_reward_per_account, harvest to accrue again
+}
+
+/**
+ * Full internal account state for owner debugging, token amounts as string
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct AccountDebug {
+    pub stake_balance: U128,
+    pub pre_reward: U128,
+    pub last_block_balance_change: BlockHeight,
+    pub unstake_balance: U128,
+    pub unstake_start_timestamp: Timestamp,
+    pub unstake_available_epoch_height: EpochHeight,
+    pub lock_tier: Option<u8>,
+    pub lock_until_epoch: EpochHeight,
+    pub locked_balance: U128,
+    pub frozen: bool,
+    pub reward_remainder: U128,
+    pub last_harvest_block: BlockHeight,
+    pub unwrap_on_harvest: bool,
+    pub referrer: Option<AccountId>,
+    pub boost_balance: U128,
+    pub operation_in_flight: bool,
+    pub compound_count: u64,
+    pub last_compound_block: BlockHeight,
+    pub vesting_balance: U128,
+    pub vested_balance: U128,
+    pub vesting_start_block: BlockHeight,
+    pub vesting_end_block: BlockHeight
+}
+
+impl From<Account> for AccountDebug {
+    fn from(account: Account) -> Self {
+        AccountDebug {
+            stake_balance: U128(account.stake_balance),
+            pre_reward: U128(account.pre_reward),
+            last_block_balance_change: account.last_block_balance_change,
+            unstake_balance: U128(account.unstake_balance),
+            unstake_start_timestamp: account.unstake_start_timestamp,
+            unstake_available_epoch_height: account.unstake_available_epoch_height,
+            lock_tier: account.lock_tier,
+            lock_until_epoch: account.lock_until_epoch,
+            locked_balance: U128(account.locked_balance),
+            frozen: account.frozen,
+            reward_remainder: U128(account.reward_remainder),
+            last_harvest_block: account.last_harvest_block,
+            unwrap_on_harvest: account.unwrap_on_harvest,
+            referrer: account.referrer,
+            boost_balance: U128(account.boost_balance),
+            operation_in_flight: account.operation_in_flight,
+            compound_count: account.compound_count,
+            last_compound_block: account.last_compound_block,
+            vesting_balance: U128(account.vesting_balance),
+            vested_balance: U128(account.vested_balance),
+            vesting_start_block: account.vesting_start_block,
+            vesting_end_block: account.vesting_end_block
+        }
+    }
+}
@@ -1,10 +1,17 @@
 use near_sdk::Gas;
+use near_sdk::serde_json::json;
 
 use crate::*;
 
 pub const FT_TRANSFER_GAS: Gas = 10_000_000_000_000;
 pub const WITHDRAW_CALLBACK_GAS: Gas = 10_000_000_000_000;
-pub const HARVEST_CALLBACK_GAS: Gas = 10_000_000_000_000;
+pub const HARVEST_CALLBACK_GAS: Gas = 30_000_000_000_000; // Cover fee transfer to treasury scheduled in callback
+pub const NEAR_WITHDRAW_GAS: Gas = 10_000_000_000_000;
+pub const STORAGE_BALANCE_OF_GAS: Gas = 5_000_000_000_000;
+pub const FT_REGISTRATION_CALLBACK_GAS: Gas = 5_000_000_000_000;
+pub const FT_BALANCE_OF_GAS: Gas = 5_000_000_000_000;
+pub const SYNC_RESERVE_CALLBACK_GAS: Gas = 5_000_000_000_000;
+pub const DEPOSIT_BALANCE_CALLBACK_GAS: Gas = 20_000_000_000_000;
 
 pub trait FungibleTokenReceiver {
     fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
@@ -13,47 +20,220 @@ pub trait FungibleTokenReceiver {
 #[ext_contract(ext_ft_contract)]
 pub trait FungibleTokenCore {
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+    fn storage_balance_of(&self, account_id: AccountId);
+    fn ft_balance_of(&self, account_id: AccountId);
+}
+
+#[ext_contract(ext_wrap_near)]
+pub trait WrapNear {
+    fn near_withdraw(&mut self, amount: U128);
 }
 
 #[ext_contract(ext_self)]
 pub trait ExtStakingContract {
-    fn ft_transfer_callback(&mut self, amount: U128, account_id: AccountId);
+    fn ft_transfer_callback(&mut self, amount: U128, account_id: AccountId, fee: U128);
     fn ft_withdraw_callback(&mut self, account_id: AccountId, old_account: Account);
+    fn near_withdraw_callback(&mut self, amount: U128, account_id: AccountId, receiver_id: AccountId, fee: U128);
+    fn ft_registration_callback(&self);
+    fn withdraw_reserve_callback(&mut self, amount: U128);
+    fn withdraw_boost_callback(&mut self, account_id: AccountId, amount: U128);
+    fn sync_reward_reserve_callback(&mut self);
+    fn storage_unregister_callback(&mut self, account_id: AccountId, amount: U128, fee: U128);
+    fn claim_vested_callback(&mut self, account_id: AccountId, amount: U128);
+    fn deposit_balance_callback(&mut self, sender_id: AccountId, amount: U128, action: TransferAction, balance_before: U128, transfer_nonce: u64);
+}
+
+/**
+ * Action of ft_on_transfer msg, empty msg is stake without lock
+ * Ex: {"action":"stake","lock_tier":2,"referrer":"carol.near"}, {"action":"fund_reward"} or {"action":"boost"}
+ * Boost is only accepted from boost token contract
+ */
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", tag = "action", rename_all = "snake_case")]
+pub enum TransferAction {
+    Stake { lock_tier: Option<u8>, referrer: Option<AccountId> },
+    FundReward,
+    Boost
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum TransferKind {
+    Harvest,
+    Withdraw,
+    Vest, // Harvested reward moved to vesting balance, nothing transferred
+    ClaimVested
+}
+
+/**
+ * Result of withdraw and harvest promise, amount is 0 if transfer failed
+ * Ex: {"account_id":"alice.near","amount":"100","kind":"harvest"}
+ */
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct TransferResult {
+    pub account_id: AccountId,
+    pub amount: U128,
+    pub kind: TransferKind
 }
 
 #[near_bindgen]
 impl FungibleTokenReceiver for StakingContract {
 
     fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
-        self.internal_deposit_and_stake(sender_id, amount.0);
+        // Nothing to stake or fund, don't touch account and reward data
+        if amount.0 == 0 {
+            return PromiseOrValue::Value(U128(0));
+        }
+
+        let action: TransferAction = if msg.is_empty() {
+            TransferAction::Stake { lock_tier: None, referrer: None }
+        } else {
+            match near_sdk::serde_json::from_str(&msg) {
+                Ok(action) => action,
+                Err(_) => {
+                    // Refund all instead of panic, so depositor get clear reason in log
+                    emit_event("invalid_msg", json!({
+                        "sender_id": sender_id,
+                        "amount": amount,
+                        "error": ContractError::InvalidMsg.as_ref()
+                    }));
+                    return PromiseOrValue::Value(amount);
+                }
+            }
+        };
+
+        // Boost check its own token contract
+        if action != TransferAction::Boost {
+            assert_eq!(self.ft_contract_id, env::predecessor_account_id(), "{}", ContractError::NotValidFtContract.as_ref());
+        }
+
+        // Fee token deliver less than amount, credit FT balance increase measured in callback
+        if self.config.fee_on_transfer_token && action != TransferAction::Boost {
+            // Pending transfer or deposit move balance while it is measured, refund so depositor retry later
+            if self.fee_deposit_in_flight || self.pending_ft_transfers > 0 {
+                emit_event("deposit_deferred", json!({
+                    "sender_id": sender_id,
+                    "amount": amount,
+                    "error": ContractError::DepositInFlight.as_ref()
+                }));
+                return PromiseOrValue::Value(amount);
+            }
+
+            // Nothing in flight, FT balance before this transfer is tracked holdings
+            let balance_before: Balance = self.internal_tracked_holdings().saturating_sub(self.ft_balance_shortfall);
+            self.fee_deposit_in_flight = true;
+            return PromiseOrValue::Promise(
+                ext_ft_contract::ft_balance_of(
+                    env::current_account_id(),
+                    &self.ft_contract_id,
+                    NO_DEPOSIT,
+                    FT_BALANCE_OF_GAS
+                ).then(
+                    ext_self::deposit_balance_callback(
+                        sender_id,
+                        amount,
+                        action,
+                        U128(balance_before),
+                        self.ft_transfer_nonce,
+                        &env::current_account_id(),
+                        NO_DEPOSIT,
+                        DEPOSIT_BALANCE_CALLBACK_GAS
+                    )
+                )
+            );
+        }
 
         // return amount not used
-        PromiseOrValue::Value(U128(0))
+        PromiseOrValue::Value(U128(self.internal_apply_transfer_action(sender_id, amount.0, action)))
     }
 }
 
 #[near_bindgen]
 impl StakingContract {
 
+    /**
+     * Apply deposit of fee token with amount actually received: FT balance minus balance_before snapshot of ft_on_transfer
+     * Deposit is serialized, no other fee token deposit or outgoing transfer was pending at snapshot
+     * Transfer started after snapshot (transfer_nonce changed) make balance unreliable, all amount is refunded same as failed query
+     * Fee part is never refunded because contract didn't receive it, it is counted in total_transfer_fee_deducted
+     */
+    #[private]
+    pub fn deposit_balance_callback(&mut self, sender_id: AccountId, amount: U128, action: TransferAction, balance_before: U128, transfer_nonce: u64) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        self.fee_deposit_in_flight = false;
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) if transfer_nonce != self.ft_transfer_nonce => {
+                emit_event("deposit_balance_check_failed", json!({
+                    "sender_id": sender_id,
+                    "amount": amount,
+                    "error": ContractError::DepositInFlight.as_ref()
+                }));
+                amount
+            },
+            PromiseResult::Successful(value) => {
+                let balance: U128 = near_sdk::serde_json::from_slice(&value).expect(ContractError::CallFailed.as_ref());
+                let received: Balance = std::cmp::min(amount.0, balance.0.saturating_sub(balance_before.0));
+                if received < amount.0 {
+                    self.total_transfer_fee_deducted += amount.0 - received;
+                    emit_event("transfer_fee_deducted", json!({
+                        "sender_id": sender_id,
+                        "amount": amount,
+                        "received": U128(received),
+                        "total_transfer_fee_deducted": U128(self.total_transfer_fee_deducted)
+                    }));
+                }
+                if received == 0 {
+                    return U128(0);
+                }
+
+                U128(self.internal_apply_transfer_action(sender_id, received, action))
+            },
+            PromiseResult::Failed => {
+                emit_event("deposit_balance_check_failed", json!({
+                    "sender_id": sender_id,
+                    "amount": amount
+                }));
+                amount
+            },
+        }
+    }
+
     #[payable]
     pub fn unstake(&mut self, amount: U128) {
         assert_one_yocto();
         let account_id: AccountId = env::predecessor_account_id();
+        assert!(self.accounts.get(&account_id).is_some(), "{}", ContractError::NotFoundAccount.as_ref());
 
         self.internal_unstake(account_id, amount.0);
     }
 
+    /**
+     * Move stake to other registered account without unstake, locked stake can't be transferred
+     */
+    #[payable]
+    pub fn transfer_stake(&mut self, to: AccountId, amount: U128) {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+
+        self.internal_transfer_stake(account_id, to, amount.0);
+    }
+
     #[payable]
     pub fn withdraw(&mut self) -> Promise {
         assert_one_yocto();
         let account_id: AccountId = env::predecessor_account_id();
+        assert!(self.accounts.get(&account_id).is_some(), "{}", ContractError::NotFoundAccount.as_ref());
         let old_account: Account = self.internal_withdraw(account_id.clone());
 
         // handle transfer withdraw
+        self.internal_start_ft_transfer();
         ext_ft_contract::ft_transfer(
             account_id.clone(), 
             U128(old_account.unstake_balance), 
-            Some(String::from("Staking contract withdraw")), 
+            Some(self.withdraw_memo.clone()), 
             &self.ft_contract_id, 
             DEPOSIT_ONE_YOCTOR, 
             FT_TRANSFER_GAS
@@ -68,71 +248,631 @@ impl StakingContract {
         )
     }
 
+    /**
+     * Transfer reward to caller, or move it to vesting balance if vesting is enabled
+     */
     #[payable]
-    pub fn harvest(&mut self) -> Promise {
+    pub fn harvest(&mut self) -> PromiseOrValue<TransferResult> {
         assert_one_yocto();
         let account_id: AccountId = env::predecessor_account_id();
-        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
-        let account: Account = Account::from(upgradable_account);
+        assert!(self.accounts.get(&account_id).is_some(), "{}", ContractError::NotFoundAccount.as_ref());
 
-        let new_reward: Balance = self.internal_calculate_account_reward(&account);
-        let current_reward: Balance = account.pre_reward + new_reward;
-        assert!(current_reward > 0, "ERR_REWARD_EQUAL_ZERO");
+        self.internal_harvest(account_id.clone(), account_id)
+    }
 
-        // Cross contract call
+    /**
+     * Harvest reward of caller to other receiver
+     * Receiver must be in allowlist if owner enable allowlist
+     * Vesting reward is kept for caller, receiver is ignored
+     */
+    #[payable]
+    pub fn harvest_to(&mut self, receiver_id: AccountId) -> PromiseOrValue<TransferResult> {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        assert!(self.accounts.get(&account_id).is_some(), "{}", ContractError::NotFoundAccount.as_ref());
+        if self.harvest_receiver_allowlist_enabled {
+            assert!(self.harvest_receiver_allowlist.contains(&receiver_id), "{}", ContractError::ReceiverNotAllowed.as_ref());
+        }
+
+        self.internal_harvest(account_id, receiver_id)
+    }
+
+    /**
+     * Transfer reward released from vesting balance to caller
+     */
+    #[payable]
+    pub fn claim_vested(&mut self) -> Promise {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        assert!(self.accounts.get(&account_id).is_some(), "{}", ContractError::NotFoundAccount.as_ref());
+        let mut account: Account = self.internal_get_account(&account_id).unwrap();
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+
+        account.release_vesting(env::block_index());
+        let amount: Balance = account.vested_balance;
+        assert!(amount > 0, "{}", ContractError::RewardEqualZero.as_ref());
+        self.internal_consume_harvest_window(amount);
+
+        account.vested_balance = 0;
+        account.operation_in_flight = true;
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+
+        self.internal_start_ft_transfer();
         ext_ft_contract::ft_transfer(
-            account_id.clone(), 
-            U128(current_reward), 
-            Some("Staking contract harvest".to_string()), 
-            &self.ft_contract_id, 
-            DEPOSIT_ONE_YOCTOR, 
+            account_id.clone(),
+            U128(amount),
+            Some(self.harvest_memo.clone()),
+            &self.ft_contract_id,
+            DEPOSIT_ONE_YOCTOR,
             FT_TRANSFER_GAS
         ).then(
-            ext_self::ft_transfer_callback(
-                U128(current_reward),
-                account_id.clone(),
-                &env::current_account_id(), 
-                NO_DEPOSIT, 
-                HARVEST_CALLBACK_GAS
+            ext_self::claim_vested_callback(
+                account_id,
+                U128(amount),
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                WITHDRAW_CALLBACK_GAS
             )
         )
     }
 
     #[private]
-    pub fn ft_transfer_callback(&mut self, amount: U128, account_id: AccountId) -> U128 {
-        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+    pub fn claim_vested_callback(&mut self, account_id: AccountId, amount: U128) -> TransferResult {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        self.internal_end_ft_transfer();
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(_value) => {
-                let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
-                let mut account: Account = Account::from(upgradable_account);
+                self.total_paid_reward_balance += amount.0;
+                self.internal_clear_operation_in_flight(&account_id);
+                TransferResult { account_id, amount, kind: TransferKind::ClaimVested }
+            },
+            PromiseResult::Failed => {
+                // Released reward is kept claimable
+                let mut account: Account = self.internal_get_account(&account_id).unwrap();
+                account.vested_balance += amount.0;
+                account.operation_in_flight = false;
+                self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+                TransferResult { account_id, amount: U128(0), kind: TransferKind::ClaimVested }
+            },
+        }
+    }
+
+    /**
+     * Restake all reward of caller into stake balance, no token transfer
+     */
+    #[payable]
+    pub fn compound(&mut self) -> U128 {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
 
-                // update account data
-                account.pre_reward = 0;
-                account.last_block_balance_change = env::block_index();
+        U128(self.internal_compound(account_id))
+    }
+
+    /**
+     * Withdraw boost token, reward is settled with old weight first
+     */
+    #[payable]
+    pub fn withdraw_boost(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        let boost_ft_contract_id: AccountId = self.boost_ft_contract_id.clone().expect(ContractError::NotValidFtContract.as_ref());
+        let mut account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        assert!(amount.0 <= account.boost_balance, "{}", ContractError::AmountExceedsBalance.as_ref());
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+
+        self.internal_settle_account_reward(&mut account);
+        account.boost_balance -= amount.0;
+        account.operation_in_flight = true;
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
 
+        ext_ft_contract::ft_transfer(
+            account_id.clone(),
+            amount,
+            Some(String::from("Staking contract withdraw boost")),
+            &boost_ft_contract_id,
+            DEPOSIT_ONE_YOCTOR,
+            FT_TRANSFER_GAS
+        ).then(
+            ext_self::withdraw_boost_callback(
+                account_id,
+                amount,
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                WITHDRAW_CALLBACK_GAS
+            )
+        )
+    }
+
+    #[private]
+    pub fn withdraw_boost_callback(&mut self, account_id: AccountId, amount: U128) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => {
+                self.internal_clear_operation_in_flight(&account_id);
+                amount
+            },
+            PromiseResult::Failed => {
+                // Token is not transferred, restore boost balance
+                let mut account: Account = self.internal_get_account(&account_id).unwrap();
+                account.boost_balance += amount.0;
+                account.operation_in_flight = false;
                 self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+                U128(0)
+            },
+        }
+    }
+
+    /**
+     * Remove account after residual reward is paid, keep account and reward if transfer failed
+     */
+    #[private]
+    pub fn storage_unregister_callback(&mut self, account_id: AccountId, amount: U128, fee: U128) -> bool {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        self.internal_end_ft_transfer();
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => {
                 self.total_paid_reward_balance += amount.0;
+                self.total_fee_collected += fee.0;
+                self.internal_transfer_to_treasury(fee.0);
+                self.internal_remove_account(account_id);
+                true
+            },
+            PromiseResult::Failed => {
+                self.internal_clear_operation_in_flight(&account_id);
+                emit_event("harvest_failed", json!({
+                    "account_id": account_id,
+                    "amount": amount
+                }));
+                false
+            },
+        }
+    }
 
-                amount
+    /**
+     * Harvest all reward and unstake all available stake in one call
+     * Unstake settle reward before stake balance change, locked stake is kept
+     * Full exit is not bound by min_unstake_amount, harvest is skipped if there is no reward
+     */
+    #[payable]
+    pub fn exit(&mut self) -> PromiseOrValue<TransferResult> {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        let account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+
+        let available_stake: Balance = account.stake_balance - account.locked_stake(env::epoch_height());
+        if available_stake > 0 {
+            self.internal_unstake_with_min(account_id.clone(), available_stake, false);
+        }
+
+        let account: Account = self.internal_get_account(&account_id).unwrap();
+        if account.pre_reward + self.internal_calculate_account_reward(&account) == 0 {
+            return PromiseOrValue::Value(TransferResult { account_id, amount: U128(0), kind: TransferKind::Harvest });
+        }
+
+        self.internal_harvest(account_id.clone(), account_id)
+    }
+
+    #[private]
+    pub fn ft_transfer_callback(&mut self, amount: U128, account_id: AccountId, fee: U128) -> TransferResult {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        self.internal_end_ft_transfer();
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => {
+                self.internal_harvest_success(account_id.clone(), amount.0, fee.0);
+
+                TransferResult { account_id, amount, kind: TransferKind::Harvest }
+            },
+            PromiseResult::Failed => {
+                // Reward keep in pre_reward, user can harvest again
+                self.internal_clear_operation_in_flight(&account_id);
+                emit_event("harvest_failed", json!({
+                    "account_id": account_id,
+                    "amount": amount
+                }));
+                TransferResult { account_id, amount: U128(0), kind: TransferKind::Harvest }
             },
-            PromiseResult::Failed => env::panic(b"ERR_CALL_FAILED"),
         }
     }
 
+    /**
+     * Callback of unwrap reward, transfer native NEAR to receiver if unwrap success
+     */
     #[private]
-    pub fn ft_withdraw_callback(&mut self, account_id: AccountId, old_account: Account) -> U128 {
-        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+    pub fn near_withdraw_callback(&mut self, amount: U128, account_id: AccountId, receiver_id: AccountId, fee: U128) -> TransferResult {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        self.internal_end_ft_transfer();
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(_value) => {
-                U128(old_account.unstake_balance)
+                self.internal_harvest_success(account_id.clone(), amount.0, fee.0);
+                Promise::new(receiver_id).transfer(amount.0);
+
+                TransferResult { account_id, amount, kind: TransferKind::Harvest }
             },
             PromiseResult::Failed => {
-                // Handle rollback data
-                self.accounts.insert(&account_id, &UpgradableAccount::from(old_account));
+                self.internal_clear_operation_in_flight(&account_id);
+                emit_event("harvest_failed", json!({
+                    "account_id": account_id,
+                    "amount": amount
+                }));
+                TransferResult { account_id, amount: U128(0), kind: TransferKind::Harvest }
+            },
+        }
+    }
+
+    /**
+     * User choose to receive harvest reward as native NEAR, only work when reward token is wrap NEAR
+     */
+    pub fn set_unwrap_on_harvest(&mut self, unwrap_on_harvest: bool) {
+        let account_id: AccountId = env::predecessor_account_id();
+        let mut account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+
+        account.unwrap_on_harvest = unwrap_on_harvest;
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+    }
+
+    /**
+     * Owner withdraw reward reserve not needed to cover stake and outstanding reward
+     */
+    #[payable]
+    pub fn owner_withdraw_reserve(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        self.assert_solvent_after(amount.0);
+
+        self.total_reward_fund -= amount.0;
+
+        self.internal_start_ft_transfer();
+        ext_ft_contract::ft_transfer(
+            self.owner_id.clone(),
+            amount,
+            Some(String::from("Staking contract withdraw reserve")),
+            &self.ft_contract_id,
+            DEPOSIT_ONE_YOCTOR,
+            FT_TRANSFER_GAS
+        ).then(
+            ext_self::withdraw_reserve_callback(
+                amount,
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                WITHDRAW_CALLBACK_GAS
+            )
+        )
+    }
+
+    #[private]
+    pub fn withdraw_reserve_callback(&mut self, amount: U128) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        self.internal_end_ft_transfer();
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => amount,
+            PromiseResult::Failed => {
+                // Token is not transferred, restore reserve
+                self.total_reward_fund += amount.0;
                 U128(0)
             },
         }
     }
-}
\ No newline at end of file
+
+    /**
+     * Owner reconcile reward reserve with FT balance of this contract, fix drift from direct ft_transfer
+     */
+    pub fn sync_reward_reserve(&mut self) -> Promise {
+        self.assert_owner();
+
+        ext_ft_contract::ft_balance_of(
+            env::current_account_id(),
+            &self.ft_contract_id,
+            NO_DEPOSIT,
+            FT_BALANCE_OF_GAS
+        ).then(
+            ext_self::sync_reward_reserve_callback(
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                SYNC_RESERVE_CALLBACK_GAS
+            )
+        )
+    }
+
+    /**
+     * Reserve is FT balance not belong to stake or unstake balance, return new reserve
+     * total_reward_fund count paid reward, so reserve = total_reward_fund - total_paid_reward_balance
+     */
+    #[private]
+    pub fn sync_reward_reserve_callback(&mut self) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                let balance: U128 = near_sdk::serde_json::from_slice(&value).expect(ContractError::CallFailed.as_ref());
+                let reserve: Balance = balance.0.saturating_sub(self.total_stake_balance + self.total_unstake_balance);
+
+                emit_event("sync_reward_reserve", json!({
+                    "old_reserve": U128(self.total_reward_fund.saturating_sub(self.total_paid_reward_balance)),
+                    "new_reserve": U128(reserve)
+                }));
+                self.total_reward_fund = reserve + self.total_paid_reward_balance;
+                self.ft_balance_shortfall = (self.total_stake_balance + self.total_unstake_balance).saturating_sub(balance.0);
+
+                U128(reserve)
+            },
+            PromiseResult::Failed => env::panic(ContractError::CallFailed.as_ref().as_bytes()),
+        }
+    }
+
+    /**
+     * Check this contract is registered for storage on FT contract, withdraw and harvest fail if not
+     */
+    pub fn check_ft_registration(&self) -> Promise {
+        ext_ft_contract::storage_balance_of(
+            env::current_account_id(),
+            &self.ft_contract_id,
+            NO_DEPOSIT,
+            STORAGE_BALANCE_OF_GAS
+        ).then(
+            ext_self::ft_registration_callback(
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                FT_REGISTRATION_CALLBACK_GAS
+            )
+        )
+    }
+
+    /**
+     * Storage balance is null if account is not registered
+     */
+    #[private]
+    pub fn ft_registration_callback(&self) -> bool {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                let storage_balance: Option<near_sdk::serde_json::Value> = near_sdk::serde_json::from_slice(&value).unwrap_or(None);
+                storage_balance.is_some()
+            },
+            PromiseResult::Failed => false,
+        }
+    }
+
+    #[private]
+    pub fn ft_withdraw_callback(&mut self, account_id: AccountId, old_account: Account) -> TransferResult {
+        assert_eq!(env::promise_results_count(), 1, "{}", ContractError::TooManyResults.as_ref());
+        self.internal_end_ft_transfer();
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => {
+                self.internal_clear_operation_in_flight(&account_id);
+                TransferResult { account_id, amount: U128(old_account.unstake_balance), kind: TransferKind::Withdraw }
+            },
+            PromiseResult::Failed => {
+                // Restore only unstake data changed in internal_withdraw, account may have changed meanwhile
+                let mut account: Account = self.internal_get_account(&account_id).unwrap();
+                account.unstake_balance += old_account.unstake_balance;
+                account.unstake_available_epoch_height = old_account.unstake_available_epoch_height;
+                account.unstake_start_timestamp = old_account.unstake_start_timestamp;
+                account.operation_in_flight = false;
+                self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+                if self.config.accrue_on_unstaking {
+                    self.internal_settle_global_reward();
+                }
+                self.total_unstake_balance += old_account.unstake_balance;
+                TransferResult { account_id, amount: U128(0), kind: TransferKind::Withdraw }
+            },
+        }
+    }
+}
+
+impl StakingContract {
+
+    /**
+     * Stake, fund reward or boost with received amount, return amount not used
+     */
+    pub(crate) fn internal_apply_transfer_action(&mut self, sender_id: AccountId, amount: Balance, action: TransferAction) -> Balance {
+        match action {
+            TransferAction::Stake { lock_tier, referrer } => {
+                if let Some(referrer_id) = referrer {
+                    self.internal_set_referrer(&sender_id, referrer_id);
+                }
+                self.internal_deposit_and_stake(sender_id, amount, lock_tier)
+            },
+            TransferAction::FundReward => {
+                self.internal_fund_reward(sender_id, amount);
+                0
+            },
+            TransferAction::Boost => {
+                self.internal_deposit_boost(sender_id, amount);
+                0
+            }
+        }
+    }
+
+    /**
+     * Transfer all reward of account to receiver, account data is updated in callback
+     * With vesting, reward is moved to vesting balance of account without transfer
+     */
+    pub(crate) fn internal_harvest(&mut self, account_id: AccountId, receiver_id: AccountId) -> PromiseOrValue<TransferResult> {
+        let mut account: Account = self.internal_get_account(&account_id).unwrap();
+        assert!(!account.frozen, "{}", ContractError::AccountFrozen.as_ref());
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+        self.assert_harvest_cooldown(&account);
+
+        let (new_reward, reward_remainder) = self.internal_calculate_account_reward_with_remainder(&account);
+        let current_reward: Balance = account.pre_reward + new_reward;
+        // Sub-unit reward is only carried in remainder, it can't be transferred yet
+        assert!(current_reward > 0 || reward_remainder == 0, "{}", ContractError::RewardBelowOneUnit.as_ref());
+        assert!(current_reward > 0, "{}", ContractError::RewardEqualZero.as_ref());
+
+        // Harvest fee is kept in reward reserve, user receive net reward
+        let fee: Balance = current_reward * Balance::from(self.config.harvest_fee_basis_points) / Balance::from(BASIS_POINTS);
+        let net_reward: Balance = current_reward - fee;
+        if let Some(vesting_blocks) = self.config.vesting_blocks {
+            self.internal_vest_reward(&account_id, net_reward, fee, vesting_blocks);
+            return PromiseOrValue::Value(TransferResult { account_id, amount: U128(net_reward), kind: TransferKind::Vest });
+        }
+        self.internal_consume_harvest_window(net_reward);
+
+        // Lock account until transfer callback, reject other harvest or withdraw meanwhile
+        account.operation_in_flight = true;
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account.clone()));
+
+        // Unwrap reward and transfer native NEAR in callback
+        if account.unwrap_on_harvest && self.wrap_near_contract_id.as_ref() == Some(&self.ft_contract_id) {
+            self.internal_start_ft_transfer();
+            return PromiseOrValue::Promise(ext_wrap_near::near_withdraw(
+                U128(net_reward),
+                &self.ft_contract_id,
+                DEPOSIT_ONE_YOCTOR,
+                NEAR_WITHDRAW_GAS
+            ).then(
+                ext_self::near_withdraw_callback(
+                    U128(net_reward),
+                    account_id.clone(),
+                    receiver_id,
+                    U128(fee),
+                    &env::current_account_id(),
+                    NO_DEPOSIT,
+                    HARVEST_CALLBACK_GAS
+                )
+            ));
+        }
+
+        // Cross contract call
+        self.internal_start_ft_transfer();
+        PromiseOrValue::Promise(ext_ft_contract::ft_transfer(
+            receiver_id, 
+            U128(net_reward), 
+            Some(self.harvest_memo.clone()), 
+            &self.ft_contract_id, 
+            DEPOSIT_ONE_YOCTOR, 
+            FT_TRANSFER_GAS
+        ).then(
+            ext_self::ft_transfer_callback(
+                U128(net_reward),
+                account_id.clone(),
+                U128(fee),
+                &env::current_account_id(), 
+                NO_DEPOSIT, 
+                HARVEST_CALLBACK_GAS
+            )
+        ))
+    }
+
+    /**
+     * Move harvested reward of account to vesting balance, released part is kept claimable
+     * Unreleased balance and new reward vest together until vesting_blocks after this block
+     * Reward is paid in claim_vested, fee is collected now
+     */
+    pub(crate) fn internal_vest_reward(&mut self, account_id: &AccountId, amount: Balance, fee: Balance, vesting_blocks: BlockHeight) {
+        let mut account: Account = self.internal_get_account(account_id).unwrap();
+
+        self.internal_settle_account_reward(&mut account);
+        account.pre_reward = account.pre_reward.saturating_sub(amount + fee);
+        account.last_harvest_block = env::block_index();
+        account.release_vesting(env::block_index());
+        account.vesting_balance += amount;
+        account.vesting_end_block = env::block_index() + vesting_blocks;
+
+        self.accounts.insert(account_id, &UpgradableAccount::from(account));
+        self.total_fee_collected += fee;
+        self.internal_transfer_to_treasury(fee);
+    }
+
+    /**
+     * Update account and contract data after reward transferred to user
+     * Reward accrued between harvest and callback block is kept in pre_reward
+     */
+    pub(crate) fn internal_harvest_success(&mut self, account_id: AccountId, amount: Balance, fee: Balance) {
+        let mut account: Account = self.internal_get_account(&account_id).unwrap();
+
+        // update account data
+        self.internal_settle_account_reward(&mut account);
+        account.pre_reward = account.pre_reward.saturating_sub(amount + fee);
+        account.last_harvest_block = env::block_index();
+        account.operation_in_flight = false;
+
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+        self.total_paid_reward_balance += amount;
+        self.total_fee_collected += fee;
+        self.internal_transfer_to_treasury(fee);
+    }
+
+    /**
+     * Transfer residual reward of account before unregister, harvest fee and cooldown are applied same as harvest
+     * Rejected if vesting is on, reward must go through harvest and claim_vested
+     */
+    pub(crate) fn internal_unregister_with_reward(&mut self, account_id: AccountId, mut account: Account, reward: Balance) -> Promise {
+        assert!(!account.frozen, "{}", ContractError::AccountFrozen.as_ref());
+        assert!(self.config.vesting_blocks.is_none(), "{}", ContractError::VestingEnabled.as_ref());
+        self.assert_harvest_cooldown(&account);
+
+        let fee: Balance = reward * Balance::from(self.config.harvest_fee_basis_points) / Balance::from(BASIS_POINTS);
+        let net_reward: Balance = reward - fee;
+        self.internal_consume_harvest_window(net_reward);
+
+        account.operation_in_flight = true;
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+
+        self.internal_start_ft_transfer();
+        ext_ft_contract::ft_transfer(
+            account_id.clone(),
+            U128(net_reward),
+            Some(self.harvest_memo.clone()),
+            &self.ft_contract_id,
+            DEPOSIT_ONE_YOCTOR,
+            FT_TRANSFER_GAS
+        ).then(
+            ext_self::storage_unregister_callback(
+                account_id,
+                U128(net_reward),
+                U128(fee),
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                HARVEST_CALLBACK_GAS
+            )
+        )
+    }
+
+    /**
+     * Move amount out of reward reserve to treasury, reserve is restored in callback if transfer fail
+     * No-op if treasury is not set
+     */
+    pub(crate) fn internal_transfer_to_treasury(&mut self, amount: Balance) {
+        let treasury_id: AccountId = match &self.treasury_id {
+            Some(treasury_id) if amount > 0 => treasury_id.clone(),
+            _ => return
+        };
+        self.total_reward_fund -= amount;
+
+        self.internal_start_ft_transfer();
+        ext_ft_contract::ft_transfer(
+            treasury_id,
+            U128(amount),
+            Some(String::from("Staking contract treasury")),
+            &self.ft_contract_id,
+            DEPOSIT_ONE_YOCTOR,
+            FT_TRANSFER_GAS
+        ).then(
+            ext_self::withdraw_reserve_callback(
+                U128(amount),
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                WITHDRAW_CALLBACK_GAS
+            )
+        );
+    }
+
+    /**
+     * Release account lock after pending transfer settled
+     */
+    pub(crate) fn internal_clear_operation_in_flight(&mut self, account_id: &AccountId) {
+        let mut account: Account = self.internal_get_account(account_id).unwrap();
+        account.operation_in_flight = false;
+        self.accounts.insert(account_id, &UpgradableAccount::from(account));
+    }
+}
@@ -2,24 +2,219 @@ use crate::*;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
 pub struct PoolInfo {
     pub total_stake_balance: U128,
+    pub total_unstake_balance: U128,
     pub total_reward: U128,
     pub total_stakers: U128,
+    pub is_paused: bool,
+    pub max_stake_per_account: Option<U128>,
+    pub harvest_fee_basis_points: u32,
+    pub total_fee_collected: U128
+}
+
+/**
+ * Account info and pool info in one call for dashboard
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct Dashboard {
+    pub account: AccountJson,
+    pub pool: PoolInfo,
+    pub pool_share_basis_points: u32
+}
+
+/**
+ * Pool info with balances formatted by FT decimals, for debugging
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct PoolInfoHuman {
+    pub total_stake_balance: String,
+    pub total_reward: String,
+    pub total_stakers: U128,
     pub is_paused: bool
 }
 
+/**
+ * Unstake countdown of account, seconds is estimated from configured epoch duration
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct UnstakeStatus {
+    pub unstake_balance: U128,
+    pub can_withdraw: bool,
+    pub unstake_available_epoch: EpochHeight,
+    pub current_epoch: EpochHeight,
+    pub epochs_remaining: EpochHeight,
+    pub estimated_seconds_remaining: u64
+}
+
+/**
+ * Pending unstake balance unlocking at one epoch
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct UnstakeTranche {
+    pub amount: U128,
+    pub available_epoch: EpochHeight,
+    pub can_withdraw: bool
+}
+
+/**
+ * Estimates to convert between block height and epoch height for countdowns
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct TimeConfig {
+    pub blocks_per_epoch: u64,
+    pub epoch_duration_seconds: u64
+}
+
+/**
+ * Reward of account split into settled pre_reward and live accrual since last change
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct RewardBreakdown {
+    pub settled: U128,
+    pub live: U128,
+    pub total: U128
+}
+
+/**
+ * Global accrual state, checked in order Paused, ReserveBreaker, BudgetExhausted
+ * ReserveBreaker: last sync_reward_reserve found FT balance not covering stake and unstake balance
+ * BudgetExhausted: reward reserve can't cover outstanding reward, or reward was never funded
+ */
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum AccrualStatus {
+    Active,
+    Paused,
+    BudgetExhausted,
+    ReserveBreaker
+}
+
+/**
+ * Inputs of global reward formula, total reward = pre_reward + total_stake_balance * reward_numerator * diff / reward_denumerator
+ * If accrue_on_unstaking, plus (total_unstake_balance * reward_numerator * diff / reward_denumerator) * reduced_rate_bp / 10000
+ * diff = min(current_block, paused_in_block if paused) - total_paused_blocks - max(last_block_balance_change, reward_start_block), 0 if reward_start_block is None
+ * All blocks are unix seconds if time_basis is PerSecond
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct RewardParams {
+    pub reward_numerator: u32,
+    pub reward_denumerator: u64,
+    pub last_block_balance_change: BlockHeight,
+    pub pre_reward: U128,
+    pub total_stake_balance: U128,
+    pub current_block: BlockHeight, // Unix seconds if time_basis is PerSecond
+    pub reward_start_block: Option<BlockHeight>,
+    pub is_paused: bool,
+    pub paused_in_block: BlockHeight,
+    pub total_paused_blocks: u64,
+    pub accrue_on_unstaking: bool,
+    pub reduced_rate_bp: u32,
+    pub total_unstake_balance: U128,
+    pub time_basis: TimeBasis
+}
+
+/**
+ * Inputs and result of account reward formula, for reproducing disputed accrual
+ * computed_reward include boost, previous config rate and carried remainder, so it can differ from plain formula
+ */
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct RewardTrace {
+    pub last_block_balance_change: BlockHeight,
+    pub current_block: BlockHeight, // Accrual clock, paused blocks are not counted
+    pub diff_block: u64, // Accrual blocks after pause and reward start adjustment
+    pub stake_balance: U128,
+    pub numerator: u32,
+    pub denumerator: u64,
+    pub computed_reward: U128,
+    pub pre_reward: U128
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct ContractMetadata {
+    pub owner_id: AccountId,
+    pub ft_contract_id: AccountId,
+    pub ft_decimals: u8,
+    pub config: Config
+}
+
 #[near_bindgen]
 impl StakingContract {
     /**
      * Get current reward by account_id
      */
-    pub fn get_account_reward(&self, account_id: AccountId) -> Balance {
+    pub fn get_account_reward(&self, account_id: AccountId) -> U128 {
         let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
         let account: Account = Account::from(upgradable_account);
         let new_reward = self.internal_calculate_account_reward(&account);
 
-        account.pre_reward + new_reward
+        U128(account.pre_reward + new_reward)
+    }
+
+    /**
+     * Current reward of many accounts in one call, unregistered account has zero reward
+     */
+    pub fn get_rewards_batch(&self, account_ids: Vec<AccountId>) -> Vec<(AccountId, U128)> {
+        assert_batch_len(account_ids.len());
+
+        account_ids
+            .into_iter()
+            .map(|account_id| {
+                let reward: Balance = match self.accounts.get(&account_id) {
+                    Some(upgradable_account) => {
+                        let account: Account = Account::from(upgradable_account);
+                        account.pre_reward + self.internal_calculate_account_reward(&account)
+                    },
+                    None => 0
+                };
+                (account_id, U128(reward))
+            })
+            .collect()
+    }
+
+    /**
+     * Reward harvest would transfer now: integer unit reward net of harvest fee, sub-unit remainder is excluded
+     */
+    pub fn get_harvestable_reward(&self, account_id: AccountId) -> U128 {
+        let account: Account = Account::from(self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref()));
+        if account.frozen {
+            return U128(0);
+        }
+
+        let reward: Balance = account.pre_reward + self.internal_calculate_account_reward(&account);
+        let fee: Balance = reward * Balance::from(self.config.harvest_fee_basis_points) / Balance::from(BASIS_POINTS);
+        U128(reward - fee)
+    }
+
+    pub fn get_reward_breakdown(&self, account_id: AccountId) -> RewardBreakdown {
+        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        let account: Account = Account::from(upgradable_account);
+        let live_reward: Balance = self.internal_calculate_account_reward(&account);
+
+        RewardBreakdown {
+            settled: U128(account.pre_reward),
+            live: U128(live_reward),
+            total: U128(account.pre_reward + live_reward)
+        }
     }
 
     pub fn get_account_info(&self, account_id: AccountId) -> AccountJson {
@@ -32,19 +227,339 @@ impl StakingContract {
             stake_balance: U128(account.stake_balance), 
             unstake_balance: U128(account.unstake_balance), 
             reward: U128(account.pre_reward + new_reward), 
-            can_withdraw: account.unstake_available_epoch_height <= env::epoch_height(),
+            can_withdraw: account.unstake_balance > 0 && account.unstake_available_epoch_height <= env::epoch_height(),
             start_unstake_timestamp: account.unstake_start_timestamp,
             unstake_available_epoch: account.unstake_available_epoch_height,
-            current_epoch: env::epoch_height()
+            current_epoch: env::epoch_height(),
+            lock_tier: account.lock_tier,
+            lock_until_epoch: account.lock_until_epoch,
+            available_to_unstake: U128(account.stake_balance - account.locked_stake(env::epoch_height())),
+            pool_share_basis_points: self.internal_pool_share_basis_points(account.stake_balance),
+            is_accrual_capped: self.internal_is_accrual_capped(&account)
+        }
+    }
+
+    pub fn get_unstake_status(&self, account_id: AccountId) -> UnstakeStatus {
+        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        let account: Account = Account::from(upgradable_account);
+
+        let epochs_remaining: EpochHeight = account.unstake_available_epoch_height.saturating_sub(env::epoch_height());
+        let epoch_duration_seconds: u64 = self.config.epoch_duration_seconds.unwrap_or(DEFAULT_EPOCH_DURATION_SECONDS);
+
+        UnstakeStatus {
+            unstake_balance: U128(account.unstake_balance),
+            can_withdraw: account.unstake_balance > 0 && epochs_remaining == 0,
+            unstake_available_epoch: account.unstake_available_epoch_height,
+            current_epoch: env::epoch_height(),
+            epochs_remaining,
+            estimated_seconds_remaining: epochs_remaining.saturating_mul(epoch_duration_seconds)
+        }
+    }
+
+    /**
+     * Epoch unstake balance can be withdrawn if account unstake now
+     */
+    pub fn preview_unlock_epoch(&self) -> EpochHeight {
+        env::epoch_height() + NUM_EPOCHS_TO_UNLOCK
+    }
+
+    /**
+     * Pending unstake tranches of account
+     * Unstake balance is kept as single balance, new unstake merge into it and reset unlock epoch,
+     * so account has at most one tranche
+     */
+    pub fn get_unstake_tranches(&self, account_id: AccountId) -> Vec<UnstakeTranche> {
+        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        let account: Account = Account::from(upgradable_account);
+        if account.unstake_balance == 0 {
+            return vec![];
+        }
+
+        vec![UnstakeTranche {
+            amount: U128(account.unstake_balance),
+            available_epoch: account.unstake_available_epoch_height,
+            can_withdraw: account.unstake_available_epoch_height <= env::epoch_height()
+        }]
+    }
+
+    /**
+     * Projected reward of account at future block with current stake and rate, no accrual while paused
+     * With PerSecond time basis blocks to target are converted to seconds, same accrual math as settlement
+     */
+    pub fn forecast_account_reward(&self, account_id: AccountId, block: BlockHeight) -> U128 {
+        assert!(block >= env::block_index(), "{}", ContractError::ForecastInPast.as_ref());
+        assert!(block - env::block_index() <= MAX_FORECAST_BLOCKS, "{}", ContractError::ForecastTooFar.as_ref());
+
+        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        let account: Account = Account::from(upgradable_account);
+        if account.frozen {
+            return U128(account.pre_reward);
+        }
+
+        let (new_reward, reward_remainder) = self.internal_calculate_account_reward_with_remainder(&account);
+        let forecast_units: u64 = if self.paused || self.reward_start_block.is_none() {
+            0
+        } else {
+            self.config.blocks_to_units(block - env::block_index())
+        };
+        let forecast_reward: U256 = self.internal_calculate_rate_reward(&account, &self.config, forecast_units) + U256::from(reward_remainder);
+
+        let forecast_total: U256 = U256::from(account.pre_reward + new_reward) + forecast_reward / U256::from(self.config.reward_precision_multiplier);
+        match self.config.max_accrued_reward_per_account {
+            Some(max_accrued_reward) if forecast_total > U256::from(max_accrued_reward.0) => U128(std::cmp::max(max_accrued_reward.0, account.pre_reward)),
+            _ => U128(u256_to_balance(forecast_total))
+        }
+    }
+
+    /**
+     * Account info with unstake and lock status evaluated at future epoch, reward is current reward
+     */
+    pub fn get_account_info_at_epoch(&self, account_id: AccountId, epoch: EpochHeight) -> AccountJson {
+        assert!(epoch >= env::epoch_height(), "{}", ContractError::ForecastInPast.as_ref());
+        assert!(epoch - env::epoch_height() <= MAX_FORECAST_EPOCHS, "{}", ContractError::ForecastTooFar.as_ref());
+
+        let account_json: AccountJson = self.get_account_info(account_id.clone());
+        let account: Account = Account::from(self.accounts.get(&account_id).unwrap());
+
+        AccountJson {
+            can_withdraw: account.unstake_balance > 0 && account.unstake_available_epoch_height <= epoch,
+            current_epoch: epoch,
+            available_to_unstake: U128(account.stake_balance - account.locked_stake(epoch)),
+            ..account_json
+        }
+    }
+
+    /**
+     * Estimated reward of staking amount for blocks with current config, without lock or token boost
+     * Blocks are converted to accrual units by time basis, reward is scaled by reward_precision_multiplier same as accrual
+     */
+    pub fn estimate_reward_for_amount(&self, amount: U128, blocks: u64) -> U128 {
+        let units: u64 = self.config.blocks_to_units(blocks);
+        let reward: U256 = calculate_reward(amount.0, self.config.reward_numerator, units, self.config.reward_precision_multiplier, self.config.reward_denumerator);
+        U128(u256_to_balance(reward / U256::from(self.config.reward_precision_multiplier)))
+    }
+
+    /**
+     * Number of blocks from last balance change needed to accrue at least one unit of reward
+     * Carried remainder count toward the unit, boost and reduced unstaking rate are applied same as accrual
+     * Return 0 if account already has reward, u64::MAX if stake can never accrue
+     */
+    pub fn blocks_until_first_reward(&self, account_id: AccountId) -> u64 {
+        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
+        let account: Account = Account::from(upgradable_account);
+
+        if account.pre_reward + self.internal_calculate_account_reward(&account) > 0 {
+            return 0;
+        }
+
+        let reward_per_unit: U256 = U256::from(account.stake_balance) * U256::from(self.config.reward_numerator) * U256::from(self.config.reward_precision_multiplier);
+        if account.frozen || reward_per_unit.is_zero() {
+            return u64::MAX;
+        }
+
+        // Scaled reward needed on top of remainder, base rate bound the answer: ceil(needed * denumerator / reward_per_unit)
+        let needed: U256 = U256::from(self.config.reward_precision_multiplier) - U256::from(account.reward_remainder);
+        let max_units: U256 = (needed * U256::from(self.config.reward_denumerator) + reward_per_unit - U256::one()) / reward_per_unit;
+        if max_units > U256::from(u64::MAX) {
+            return u64::MAX;
         }
+
+        // Boost only shorten it, search smallest units reaching needed reward
+        let (mut low, mut high): (u64, u64) = (1, max_units.as_u64());
+        while low < high {
+            let middle: u64 = low + (high - low) / 2;
+            if self.internal_calculate_rate_reward(&account, &self.config, middle) >= needed {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+
+        self.config.units_to_blocks(low)
+    }
+
+    /**
+     * Estimated APY in basis points of account compounding at its observed frequency, without lock or token boost
+     * Frequency is compound_count over accrual time from reward start to last compound, nominal APR if never compound
+     */
+    pub fn get_effective_apy(&self, account_id: AccountId) -> u32 {
+        let account: Account = Account::from(self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref()));
+        let apr_basis_points: u32 = self.config.effective_apr_basis_points();
+        let reward_start_block: BlockHeight = match self.reward_start_block {
+            Some(reward_start_block) if account.compound_count > 0 => reward_start_block,
+            _ => return apr_basis_points
+        };
+
+        let elapsed: u64 = std::cmp::max(account.last_compound_block.saturating_sub(reward_start_block), 1);
+        let units_per_year: u64 = self.config.units_per_year();
+        let periods: u64 = (U256::from(account.compound_count) * U256::from(units_per_year) / U256::from(elapsed))
+            .min(U256::from(units_per_year))
+            .max(U256::one())
+            .as_u64();
+
+        compound_apy_basis_points(apr_basis_points, periods)
+    }
+
+    pub fn get_accrual_status(&self) -> AccrualStatus {
+        if self.paused {
+            return AccrualStatus::Paused;
+        }
+        if self.ft_balance_shortfall > 0 {
+            return AccrualStatus::ReserveBreaker;
+        }
+
+        let reserve: Balance = self.total_reward_fund.saturating_sub(self.total_paid_reward_balance);
+        let accrued_reward: Balance = self.pre_reward + self.internal_calculate_global_reward();
+        let outstanding_reward: Balance = accrued_reward.saturating_sub(self.total_paid_reward_balance + self.total_fee_collected);
+        if self.reward_start_block.is_none() || outstanding_reward >= reserve {
+            return AccrualStatus::BudgetExhausted;
+        }
+
+        AccrualStatus::Active
     }
 
     pub fn get_pool_info(&self) -> PoolInfo {
         PoolInfo { 
             total_stake_balance: U128(self.total_stake_balance), 
+            total_unstake_balance: U128(self.total_unstake_balance),
             total_reward: U128(self.pre_reward + self.internal_calculate_global_reward()), 
             total_stakers: U128(self.total_staker), 
-            is_paused: self.paused
+            is_paused: self.paused,
+            max_stake_per_account: self.config.max_stake_per_account,
+            harvest_fee_basis_points: self.config.harvest_fee_basis_points,
+            total_fee_collected: U128(self.total_fee_collected)
         }
     }
+
+    pub fn get_reward_params(&self) -> RewardParams {
+        RewardParams {
+            reward_numerator: self.config.reward_numerator,
+            reward_denumerator: self.config.reward_denumerator,
+            last_block_balance_change: self.last_block_balance_change,
+            pre_reward: U128(self.pre_reward),
+            total_stake_balance: U128(self.total_stake_balance),
+            current_block: self.config.reward_clock(),
+            reward_start_block: self.reward_start_block,
+            is_paused: self.paused,
+            paused_in_block: self.paused_in_block,
+            total_paused_blocks: self.total_paused_blocks,
+            accrue_on_unstaking: self.config.accrue_on_unstaking,
+            reduced_rate_bp: self.config.reduced_rate_bp,
+            total_unstake_balance: U128(self.total_unstake_balance),
+            time_basis: self.config.time_basis
+        }
+    }
+
+    pub fn get_metadata(&self) -> ContractMetadata {
+        ContractMetadata {
+            owner_id: self.owner_id.clone(),
+            ft_contract_id: self.ft_contract_id.clone(),
+            ft_decimals: self.ft_decimals,
+            config: self.config
+        }
+    }
+
+    pub fn get_time_config(&self) -> TimeConfig {
+        TimeConfig {
+            blocks_per_epoch: self.config.estimated_blocks_per_epoch.unwrap_or(DEFAULT_BLOCKS_PER_EPOCH),
+            epoch_duration_seconds: self.config.epoch_duration_seconds.unwrap_or(DEFAULT_EPOCH_DURATION_SECONDS)
+        }
+    }
+
+    pub fn get_lock_tiers(&self) -> Vec<LockTier> {
+        self.lock_tiers.clone()
+    }
+
+    pub fn get_config_history(&self, from_index: u64, limit: u64) -> Vec<(BlockHeight, Config)> {
+        (from_index..std::cmp::min(from_index + limit, self.config_history.len()))
+            .map(|index| self.config_history.get(index).unwrap())
+            .collect()
+    }
+
+    /**
+     * Effective APR in basis points at each config change, for charting
+     */
+    pub fn get_apr_history(&self, from_index: u64, limit: u64) -> Vec<(BlockHeight, u32)> {
+        self.get_config_history(from_index, limit)
+            .into_iter()
+            .map(|(block, config)| (block, config.effective_apr_basis_points()))
+            .collect()
+    }
+
+    pub fn get_pool_info_human(&self) -> PoolInfoHuman {
+        let pool_info: PoolInfo = self.get_pool_info();
+
+        PoolInfoHuman {
+            total_stake_balance: format_decimal(pool_info.total_stake_balance.0, self.ft_decimals),
+            total_reward: format_decimal(pool_info.total_reward.0, self.ft_decimals),
+            total_stakers: pool_info.total_stakers,
+            is_paused: pool_info.is_paused
+        }
+    }
+
+    pub fn get_number_of_accounts(&self) -> u64 {
+        self.accounts.len()
+    }
+
+    pub fn get_accounts(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let account_ids = self.accounts.keys_as_vector();
+        (from_index..std::cmp::min(from_index + limit, account_ids.len()))
+            .map(|index| account_ids.get(index).unwrap())
+            .collect()
+    }
+
+    /**
+     * Accounts in index range [from_index, from_index + limit) have unstake balance ready to withdraw
+     * Page over account index, so result can be shorter than limit
+     */
+    pub fn get_withdrawable_accounts(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let account_ids = self.accounts.keys_as_vector();
+        let account_values = self.accounts.values_as_vector();
+        (from_index..std::cmp::min(from_index + limit, account_ids.len()))
+            .filter(|index| {
+                let account: Account = Account::from(account_values.get(*index).unwrap());
+                account.unstake_balance > 0 && account.unstake_available_epoch_height <= env::epoch_height()
+            })
+            .map(|index| account_ids.get(index).unwrap())
+            .collect()
+    }
+
+    /**
+     * Count accounts have stake balance, to detect drift of total_staker
+     * O(n) with number of accounts, for audit only
+     */
+    pub fn recount_stakers(&self) -> u64 {
+        self.accounts
+            .values()
+            .map(Account::from)
+            .filter(|account| account.stake_balance > 0)
+            .count() as u64
+    }
+
+    pub fn get_dashboard(&self, account_id: AccountId) -> Dashboard {
+        let account: AccountJson = self.get_account_info(account_id);
+        let pool_share_basis_points: u32 = account.pool_share_basis_points;
+
+        Dashboard {
+            account,
+            pool: self.get_pool_info(),
+            pool_share_basis_points
+        }
+    }
+
+    /**
+     * Rank of account by stake balance, accounts with same stake have same rank
+     * O(n) with number of accounts, for modest pools
+     */
+    pub fn get_staker_rank(&self, account_id: AccountId) -> u64 {
+        let account: Account = Account::from(self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref()));
+
+        let greater_stakers = self.accounts
+            .values()
+            .map(Account::from)
+            .filter(|other| other.stake_balance > account.stake_balance)
+            .count() as u64;
+
+        greater_stakers + 1
+    }
 }
\ No newline at end of file
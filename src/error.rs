@@ -0,0 +1,108 @@
+/**
+ * Error messages of staking contract
+ * String value keep same with old message for client compatibility
+ */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ContractError {
+    NotFoundAccount,
+    ContractPaused,
+    NotValidFtContract,
+    AmountExceedsBalance,
+    UnstakeBalanceIsZero,
+    DisableWithdraw,
+    RewardEqualZero,
+    TooManyResults,
+    CallFailed,
+    OnlyOwnerContract,
+    ReadContractState,
+    AmountLessThanMinStaking,
+    InvalidMsg,
+    InvalidLockTier,
+    StakeLocked,
+    BatchTooLarge,
+    AccountFrozen,
+    InvalidConfig,
+    HarvestCooldown,
+    AmountLessThanMinUnstake,
+    ContractNotPaused,
+    RewardOverflow,
+    ReceiverNotAllowed,
+    SelfReferral,
+    ReferrerAlreadySet,
+    TotalStakeUnderflow,
+    StakeBalanceUnderflow,
+    PoolNotEmpty,
+    Insolvent,
+    ForecastInPast,
+    ForecastTooFar,
+    InvalidInitAccounts,
+    OperationInFlight,
+    AccountNotEmpty,
+    RewardNotHarvested,
+    AprMismatch,
+    SelfStakeTransfer,
+    ExceedMaxStake,
+    PoolHarvestThrottled,
+    InsufficientContractBalance,
+    AlreadyInitialized,
+    MaxAccounts,
+    RewardBelowOneUnit,
+    VestingEnabled,
+    DepositInFlight,
+    ReferrerAfterStake,
+    ConfigHistoryFull
+}
+
+impl AsRef<str> for ContractError {
+    fn as_ref(&self) -> &str {
+        match self {
+            ContractError::NotFoundAccount => "ERR_NOT_FOUND_ACCOUNT",
+            ContractError::ContractPaused => "ERR_CONTRACT_PAUSED",
+            ContractError::NotValidFtContract => "ERR_NOT_VALID_FT_CONTRACT",
+            ContractError::AmountExceedsBalance => "ERR_AMOUNT_MUST_LESS_THAN_BALANCE",
+            ContractError::UnstakeBalanceIsZero => "ERR_UNSTAKE_BALANCE_IS_ZERO",
+            ContractError::DisableWithdraw => "ERR_DISABLE_WITHDRAW",
+            ContractError::RewardEqualZero => "ERR_REWARD_EQUAL_ZERO",
+            ContractError::TooManyResults => "ERR_TOO_MANY_RESULTS",
+            ContractError::CallFailed => "ERR_CALL_FAILED",
+            ContractError::OnlyOwnerContract => "ERR_ONLY_OWNER_CONTRACT",
+            ContractError::ReadContractState => "ERR_READ_CONTRACT_STATE",
+            ContractError::AmountLessThanMinStaking => "ERR_AMOUNT_LESS_THAN_MIN_STAKING",
+            ContractError::InvalidMsg => "ERR_INVALID_MSG",
+            ContractError::InvalidLockTier => "ERR_INVALID_LOCK_TIER",
+            ContractError::StakeLocked => "ERR_STAKE_LOCKED",
+            ContractError::BatchTooLarge => "ERR_BATCH_TOO_LARGE",
+            ContractError::AccountFrozen => "ERR_ACCOUNT_FROZEN",
+            ContractError::InvalidConfig => "ERR_INVALID_CONFIG",
+            ContractError::HarvestCooldown => "ERR_HARVEST_COOLDOWN",
+            ContractError::AmountLessThanMinUnstake => "ERR_AMOUNT_LESS_THAN_MIN_UNSTAKE",
+            ContractError::ContractNotPaused => "ERR_CONTRACT_NOT_PAUSED",
+            ContractError::RewardOverflow => "ERR_REWARD_OVERFLOW",
+            ContractError::ReceiverNotAllowed => "ERR_RECEIVER_NOT_ALLOWED",
+            ContractError::SelfReferral => "ERR_SELF_REFERRAL",
+            ContractError::ReferrerAlreadySet => "ERR_REFERRER_ALREADY_SET",
+            ContractError::TotalStakeUnderflow => "ERR_TOTAL_STAKE_UNDERFLOW",
+            ContractError::StakeBalanceUnderflow => "ERR_STAKE_BALANCE_UNDERFLOW",
+            ContractError::PoolNotEmpty => "ERR_POOL_NOT_EMPTY",
+            ContractError::Insolvent => "ERR_INSOLVENT",
+            ContractError::ForecastInPast => "ERR_FORECAST_IN_PAST",
+            ContractError::ForecastTooFar => "ERR_FORECAST_TOO_FAR",
+            ContractError::InvalidInitAccounts => "ERR_INVALID_INIT_ACCOUNTS",
+            ContractError::OperationInFlight => "ERR_OPERATION_IN_FLIGHT",
+            ContractError::AccountNotEmpty => "ERR_ACCOUNT_NOT_EMPTY",
+            ContractError::RewardNotHarvested => "ERR_REWARD_NOT_HARVESTED",
+            ContractError::AprMismatch => "ERR_APR_MISMATCH",
+            ContractError::SelfStakeTransfer => "ERR_SELF_STAKE_TRANSFER",
+            ContractError::ExceedMaxStake => "ERR_EXCEED_MAX_STAKE",
+            ContractError::PoolHarvestThrottled => "ERR_POOL_HARVEST_THROTTLED",
+            ContractError::InsufficientContractBalance => "ERR_INSUFFICIENT_CONTRACT_BALANCE",
+            ContractError::AlreadyInitialized => "ERR_ALREADY_INITIALIZED",
+            ContractError::MaxAccounts => "ERR_MAX_ACCOUNTS",
+            ContractError::RewardBelowOneUnit => "ERR_REWARD_BELOW_ONE_UNIT",
+            ContractError::VestingEnabled => "ERR_VESTING_ENABLED",
+            ContractError::DepositInFlight => "ERR_DEPOSIT_IN_FLIGHT",
+            ContractError::ReferrerAfterStake => "ERR_REFERRER_AFTER_STAKE",
+            ContractError::ConfigHistoryFull => "ERR_CONFIG_HISTORY_FULL"
+        }
+    }
+}
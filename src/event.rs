@@ -0,0 +1,21 @@
+use near_sdk::serde_json::{json, Value};
+
+use crate::*;
+
+pub const EVENT_STANDARD: &str = "staking-contract";
+pub const EVENT_VERSION: &str = "1.0.0";
+
+/**
+ * Log event in NEP-297 format for indexers
+ * EVENT_JSON:{"standard":"staking-contract","version":"1.0.0","event":"...","data":[...]}
+ */
+pub(crate) fn emit_event(event: &str, data: Value) {
+    let event_json = json!({
+        "standard": EVENT_STANDARD,
+        "version": EVENT_VERSION,
+        "event": event,
+        "data": [data]
+    });
+
+    env::log(format!("EVENT_JSON:{}", event_json.to_string()).as_bytes());
+}
@@ -1,3 +1,5 @@
+use near_sdk::serde_json::json;
+
 use crate::*;
 
 impl StakingContract {
@@ -7,30 +9,50 @@ impl StakingContract {
      * Handle use transfer token to staking contract
      * 1. validate data
      * 2. handle stake
+     * Return amount not staked because of max stake per account
      */
-    pub(crate) fn internal_deposit_and_stake(&mut self, account_id: AccountId, amount: Balance) {
+    pub(crate) fn internal_deposit_and_stake(&mut self, account_id: AccountId, amount: Balance, lock_tier: Option<u8>) -> Balance {
 
         let upgradable_account: Option<UpgradableAccount> = self.accounts.get(&account_id);
-        assert!(upgradable_account.is_some(), "ERR_NOT_FOUND_ACCOUNT");
-        assert!(!self.paused, "ERR_CONTRACT_PAUSED");
-        assert_eq!(self.ft_contract_id, env::predecessor_account_id(), "ERR_NOT_VALID_FT_CONTRACT");
+        assert!(upgradable_account.is_some(), "{}", ContractError::NotFoundAccount.as_ref());
+        assert!(!self.paused, "{}", ContractError::ContractPaused.as_ref());
+        assert!(amount >= self.config.min_staking_amount.0, "{}", ContractError::AmountLessThanMinStaking.as_ref());
 
         // Check account exists
-        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
-        let mut account = Account::from(upgradable_account);
+        let mut account: Account = self.internal_get_account(&account_id).unwrap();
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
 
-        if account.stake_balance == 0 {
+        // Only accept amount keep account under max stake
+        let unused_amount: Balance = match self.config.max_stake_per_account {
+            Some(max_stake) if account.stake_balance + amount > max_stake.0 => {
+                account.stake_balance + amount - std::cmp::max(max_stake.0, account.stake_balance)
+            },
+            _ => 0
+        };
+        let amount: Balance = amount - unused_amount;
+        if amount == 0 {
+            return unused_amount;
+        }
+
+        let is_first_stake: bool = account.stake_balance == 0;
+        if is_first_stake {
             self.total_staker += 1;
         }
 
-        // if exist account, update balance and update pre data
-        let new_reward: Balance = self.internal_calculate_account_reward(&account);
+        // if exist account, settle reward and update pre data
+        // Settle move last change to this block, so first stake accrue from stake block, not from registration
+        self.internal_settle_account_reward(&mut account);
 
         // update account data
-        account.pre_stake_balance = account.stake_balance;
-        account.pre_reward += new_reward;
         account.stake_balance += amount;
-        account.last_block_balance_change = env::block_index();
+
+        // Lock deposit amount until end of tier, keep the longer lock if account already locked
+        if let Some(tier_index) = lock_tier {
+            let tier: LockTier = *self.lock_tiers.get(tier_index as usize).expect(ContractError::InvalidLockTier.as_ref());
+            account.locked_balance = account.locked_stake(env::epoch_height()) + amount;
+            account.lock_tier = Some(tier_index);
+            account.lock_until_epoch = std::cmp::max(account.lock_until_epoch, env::epoch_height() + tier.lock_epochs);
+        }
         self.accounts.insert(&account_id, &UpgradableAccount::from(account));
 
 
@@ -38,28 +60,52 @@ impl StakingContract {
         let new_contract_reward: Balance = self.internal_calculate_global_reward();
         self.total_stake_balance += amount;
         self.pre_reward += new_contract_reward;
-        self.last_block_balance_change = env::block_index();
+        self.last_block_balance_change = self.internal_accrual_clock();
+
+        emit_event("stake", json!({
+            "account_id": account_id,
+            "amount": U128(amount),
+            "is_first_stake": is_first_stake
+        }));
 
+        unused_amount
     }
 
     pub(crate) fn internal_unstake(&mut self, account_id: AccountId, amount: Balance) {
-        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
+        self.internal_unstake_with_min(account_id, amount, true);
+    }
 
-        let mut account = Account::from(upgradable_account);
+    /**
+     * Unstake amount of account, min_unstake_amount is skipped when exit unstake all available stake
+     */
+    pub(crate) fn internal_unstake_with_min(&mut self, account_id: AccountId, amount: Balance, enforce_min_unstake: bool) {
+        let mut account: Account = self.internal_get_account(&account_id).unwrap();
 
-        assert!(amount <= account.stake_balance, "ERR_AMOUNT_MUST_LESS_THAN_BALANCE");
+        assert!(!account.frozen, "{}", ContractError::AccountFrozen.as_ref());
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+        assert!(amount <= account.stake_balance, "{}", ContractError::AmountExceedsBalance.as_ref());
+        assert!(
+            !enforce_min_unstake || amount >= self.config.min_unstake_amount.0 || amount == account.stake_balance,
+            "{}", ContractError::AmountLessThanMinUnstake.as_ref()
+        );
+        let available_stake: Balance = account.stake_balance - account.locked_stake(env::epoch_height());
+        assert!(amount <= available_stake, "{}", ContractError::StakeLocked.as_ref());
 
-        // if exist account, update balance and update pre data
-        let new_reward: Balance = self.internal_calculate_account_reward(&account);
+        // if exist account, settle reward and update pre data
+        // Sub-unit reward is kept in reward_remainder, so short interval before unstake doesn't forfeit it
+        self.internal_settle_account_reward(&mut account);
 
         // update account data
-        account.pre_stake_balance = account.stake_balance;
-        account.pre_reward += new_reward;
-        account.stake_balance -= amount;
-        account.last_block_balance_change = env::block_index();
-        account.unstake_available_epoch_height = env::epoch_height() + NUM_EPOCHS_TO_UNLOCK;
+        account.stake_balance = account.stake_balance.checked_sub(amount).expect(ContractError::StakeBalanceUnderflow.as_ref());
+        account.unstake_available_epoch_height = self.preview_unlock_epoch();
         account.unstake_balance += amount;
         account.unstake_start_timestamp = env::block_timestamp();
+
+        // Lock expired, stop boost reward
+        if account.lock_until_epoch <= env::epoch_height() {
+            account.lock_tier = None;
+            account.locked_balance = 0;
+        }
         
         if account.stake_balance == 0 {
             self.total_staker -= 1;
@@ -70,64 +116,563 @@ impl StakingContract {
 
         // update contract data
         let new_contract_reward: Balance = self.internal_calculate_global_reward();
-        self.total_stake_balance -= amount;
+        // Global total must cover account stake, fail explicit if state is out of sync
+        self.total_stake_balance = self.total_stake_balance.checked_sub(amount).expect(ContractError::TotalStakeUnderflow.as_ref());
+        self.total_unstake_balance += amount;
         self.pre_reward += new_contract_reward;
-        self.last_block_balance_change = env::block_index();
+        self.last_block_balance_change = self.internal_accrual_clock();
+    }
+
+    /**
+     * Move stake between accounts, reward of both accounts is settled with old stake first
+     * Total stake balance is unchanged so global reward is not settled
+     */
+    pub(crate) fn internal_transfer_stake(&mut self, account_id: AccountId, receiver_id: AccountId, amount: Balance) {
+        assert_ne!(account_id, receiver_id, "{}", ContractError::SelfStakeTransfer.as_ref());
+        let mut account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        let receiver: Account = self.internal_get_account(&receiver_id).expect(ContractError::NotFoundAccount.as_ref());
+
+        assert!(!account.frozen && !receiver.frozen, "{}", ContractError::AccountFrozen.as_ref());
+        // Failed pending transfer restore account in callback, stake moved meanwhile would be counted twice
+        assert!(!account.operation_in_flight && !receiver.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+        assert!(amount <= account.stake_balance, "{}", ContractError::AmountExceedsBalance.as_ref());
+        let available_stake: Balance = account.stake_balance - account.locked_stake(env::epoch_height());
+        assert!(amount <= available_stake, "{}", ContractError::StakeLocked.as_ref());
+        if let Some(max_stake) = self.config.max_stake_per_account {
+            assert!(receiver.stake_balance + amount <= max_stake.0, "{}", ContractError::ExceedMaxStake.as_ref());
+        }
+
+        // Settle may credit referral bonus to the other account, so each account is stored before the other is loaded
+        self.internal_settle_account_reward(&mut account);
+        account.stake_balance -= amount;
+        if account.stake_balance == 0 && amount > 0 {
+            self.total_staker -= 1;
+        }
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+
+        let mut receiver: Account = self.internal_get_account(&receiver_id).unwrap();
+        self.internal_settle_account_reward(&mut receiver);
+        if receiver.stake_balance == 0 && amount > 0 {
+            self.total_staker += 1;
+        }
+        receiver.stake_balance += amount;
+        self.accounts.insert(&receiver_id, &UpgradableAccount::from(receiver));
+
+        emit_event("stake_transfer", json!({
+            "from": account_id,
+            "to": receiver_id,
+            "amount": U128(amount)
+        }));
     }
 
     pub(crate) fn internal_withdraw(&mut self, account_id: AccountId) -> Account {
-        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
-        let account: Account = Account::from(upgradable_account);
+        let account: Account = self.internal_get_account(&account_id).unwrap();
+
+        assert!(account.unstake_balance > 0, "{}", ContractError::UnstakeBalanceIsZero.as_ref());
+        assert!(account.unstake_available_epoch_height <= env::epoch_height(), "{}", ContractError::DisableWithdraw.as_ref());
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+        self.assert_contract_balance_covers(account.unstake_balance);
 
-        assert!(account.unstake_balance > 0, "ERR_UNSTAKE_BALANCE_IS_ZERO");
-        assert!(account.unstake_available_epoch_height <= env::epoch_height(), "ERR_DISABLE_WITHDRAW");
+        // Settle reward of unstaking balance before it is removed
+        let mut settled_account: Account = account.clone();
+        if self.config.accrue_on_unstaking {
+            self.internal_settle_account_reward(&mut settled_account);
+            self.internal_settle_global_reward();
+        }
 
         let new_account: Account = Account {
-            pre_reward: account.pre_reward,
-            stake_balance: account.stake_balance,
-            pre_stake_balance: account.pre_stake_balance,
-            last_block_balance_change: account.last_block_balance_change,
             unstake_balance: 0,
             unstake_start_timestamp: 0,
-            unstake_available_epoch_height: 0
+            unstake_available_epoch_height: 0,
+            operation_in_flight: true,
+            ..settled_account
         };
 
         self.accounts.insert(&account_id, &UpgradableAccount::from(new_account));
+        self.total_unstake_balance -= account.unstake_balance;
 
         account
     }
 
     pub(crate) fn internal_calculate_account_reward(&self, account: &Account) -> Balance {
-        let lasted_block = if self.paused {
-            self.paused_in_block
-        } else {
-            env::block_index()
-        };
-        let diff_block = lasted_block - account.last_block_balance_change;
-        let reward: U256 = (U256::from(self.total_stake_balance) * U256::from(self.config.reward_numerator) * U256::from(diff_block)) / U256::from(self.config.reward_denumerator);
-        reward.as_u128()
+        self.internal_calculate_account_reward_with_remainder(account).0
     }
 
+    /**
+     * Calculate new reward of account and remainder carry to next settlement
+     * Reward is scaled by reward_precision_multiplier, remainder is in scaled unit
+     */
+    pub(crate) fn internal_calculate_account_reward_with_remainder(&self, account: &Account) -> (Balance, Balance) {
+        // Frozen account only keep settled pre_reward
+        if account.frozen {
+            return (0, account.reward_remainder);
+        }
+
+        // Blocks of each config interval since last change accrue at rate of that config, walk history from newest
+        // Oldest config also cover blocks before it, ex: account migrated from old contract
+        let mut reward: U256 = U256::from(account.reward_remainder);
+        let mut interval_end_diff_block: u64 = 0;
+        let mut index: u64 = self.config_history.len();
+        while index > 0 {
+            index -= 1;
+            let (config_block, config) = self.config_history.get(index).unwrap();
+            let interval_start: BlockHeight = if index == 0 { account.last_block_balance_change } else { std::cmp::max(account.last_block_balance_change, config_block) };
+            let interval_start_diff_block: u64 = std::cmp::max(self.effective_diff_block(interval_start), interval_end_diff_block);
+
+            // Newest interval use current config, it may be changed without new history entry
+            let rate_config: &Config = if index + 1 == self.config_history.len() { &self.config } else { &config };
+            reward += self.internal_calculate_rate_reward(account, rate_config, interval_start_diff_block - interval_end_diff_block);
+            interval_end_diff_block = interval_start_diff_block;
+
+            if config_block <= account.last_block_balance_change {
+                break;
+            }
+        }
+
+        let multiplier: U256 = U256::from(self.config.reward_precision_multiplier);
+
+        // Accrual halt at cap until account harvest, remainder is dropped
+        if let Some(max_accrued_reward) = self.config.max_accrued_reward_per_account {
+            let room: Balance = max_accrued_reward.0.saturating_sub(account.pre_reward);
+            if reward / multiplier >= U256::from(room) {
+                return (room, 0);
+            }
+        }
+
+        (u256_to_balance(reward / multiplier), u256_to_balance(reward % multiplier))
+    }
+
+    /**
+     * Account reward reached max_accrued_reward_per_account, no more accrual until harvest
+     */
+    pub(crate) fn internal_is_accrual_capped(&self, account: &Account) -> bool {
+        match self.config.max_accrued_reward_per_account {
+            Some(max_accrued_reward) => account.pre_reward + self.internal_calculate_account_reward(account) >= max_accrued_reward.0,
+            None => false
+        }
+    }
+
+    /**
+     * Reward of account for diff_block at rate of config, scaled by current reward_precision_multiplier
+     */
+    pub(crate) fn internal_calculate_rate_reward(&self, account: &Account, config: &Config, diff_block: BlockHeight) -> U256 {
+        if diff_block == 0 {
+            return U256::zero();
+        }
+
+        let reward: U256 = calculate_reward(account.stake_balance, config.reward_numerator, diff_block, self.config.reward_precision_multiplier, config.reward_denumerator);
+
+        // Boost reward of locked part by tier, then whole reward by boost token
+        let boost_basis_points: u32 = self.internal_lock_boost(account);
+        let locked_reward: U256 = calculate_reward(account.locked_stake(env::epoch_height()), config.reward_numerator, diff_block, self.config.reward_precision_multiplier, config.reward_denumerator);
+        let token_boost_basis_points: u32 = self.internal_token_boost(account);
+        let mut boosted_reward: U256 = (reward + locked_reward * U256::from(boost_basis_points - BASIS_POINTS) / U256::from(BASIS_POINTS))
+            * U256::from(BASIS_POINTS + token_boost_basis_points) / U256::from(BASIS_POINTS);
+
+        // Unstaking balance accrue at reduced rate until withdraw
+        if config.accrue_on_unstaking {
+            let unstake_reward: U256 = calculate_reward(account.unstake_balance, config.reward_numerator, diff_block, self.config.reward_precision_multiplier, config.reward_denumerator);
+            boosted_reward += unstake_reward * U256::from(config.reduced_rate_bp) / U256::from(BASIS_POINTS);
+        }
+
+        boosted_reward
+    }
+
+    /**
+     * Fold new reward of account into pre_reward, keep remainder and move last change to current block
+     * Called before every stake balance change, so pre_reward is sum of balance * blocks of each interval (time-weighted)
+     */
+    pub(crate) fn internal_settle_account_reward(&mut self, account: &mut Account) {
+        let (new_reward, reward_remainder) = self.internal_calculate_account_reward_with_remainder(account);
+        account.pre_reward += new_reward;
+        account.reward_remainder = reward_remainder;
+        account.last_block_balance_change = self.internal_accrual_clock();
+
+        // Credit referral bonus of settled reward to referrer, drop referrer if it unregistered
+        if let Some(referrer_id) = account.referrer.clone() {
+            let referral_bonus: Balance = new_reward * Balance::from(self.config.referral_bonus_bp) / Balance::from(BASIS_POINTS);
+            if referral_bonus > 0 {
+                match self.internal_get_account(&referrer_id) {
+                    Some(mut referrer) => {
+                        referrer.pre_reward += referral_bonus;
+                        self.accounts.insert(&referrer_id, &UpgradableAccount::from(referrer));
+                    },
+                    None => account.referrer = None
+                }
+            }
+        }
+    }
+
+    /**
+     * Restake all reward of account, reward is moved from reward reserve to stake balance
+     * Harvest fee, cooldown and pool harvest window are applied same as harvest, return restaked amount
+     * Rejected if vesting is on, restaked reward could be unstaked before it vest
+     */
+    pub(crate) fn internal_compound(&mut self, account_id: AccountId) -> Balance {
+        assert!(!self.paused, "{}", ContractError::ContractPaused.as_ref());
+        assert!(self.config.vesting_blocks.is_none(), "{}", ContractError::VestingEnabled.as_ref());
+        let mut account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        assert!(!account.frozen, "{}", ContractError::AccountFrozen.as_ref());
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+        self.assert_harvest_cooldown(&account);
+
+        self.internal_settle_account_reward(&mut account);
+        let reward: Balance = account.pre_reward;
+        assert!(reward > 0, "{}", ContractError::RewardEqualZero.as_ref());
+
+        let fee: Balance = reward * Balance::from(self.config.harvest_fee_basis_points) / Balance::from(BASIS_POINTS);
+        let net_reward: Balance = reward - fee;
+        if let Some(max_stake) = self.config.max_stake_per_account {
+            assert!(account.stake_balance + net_reward <= max_stake.0, "{}", ContractError::ExceedMaxStake.as_ref());
+        }
+        assert!(self.total_paid_reward_balance + reward <= self.total_reward_fund, "{}", ContractError::Insolvent.as_ref());
+        self.internal_consume_harvest_window(net_reward);
+
+        if account.stake_balance == 0 && net_reward > 0 {
+            self.total_staker += 1;
+        }
+        account.pre_reward = 0;
+        account.stake_balance += net_reward;
+        account.last_harvest_block = env::block_index();
+        account.compound_count += 1;
+        account.last_compound_block = self.internal_accrual_clock();
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+
+        // Settle global reward with old total stake before it grow
+        self.internal_settle_global_reward();
+        self.total_stake_balance += net_reward;
+        self.total_paid_reward_balance += net_reward;
+        self.total_fee_collected += fee;
+        self.internal_transfer_to_treasury(fee);
+
+        emit_event("compound", json!({
+            "account_id": account_id,
+            "amount": U128(net_reward),
+            "fee": U128(fee)
+        }));
+
+        net_reward
+    }
+
+    /**
+     * Set referrer of account, referrer can't be changed after set or set after account staked
+     * Same referrer again is no-op, so later stake msg can repeat it
+     */
+    pub(crate) fn internal_set_referrer(&mut self, account_id: &AccountId, referrer_id: AccountId) {
+        assert_ne!(account_id, &referrer_id, "{}", ContractError::SelfReferral.as_ref());
+        assert!(self.accounts.get(&referrer_id).is_some(), "{}", ContractError::NotFoundAccount.as_ref());
+
+        let mut account: Account = self.internal_get_account(account_id).expect(ContractError::NotFoundAccount.as_ref());
+        match account.referrer.as_ref() {
+            Some(current_referrer_id) => {
+                assert_eq!(current_referrer_id, &referrer_id, "{}", ContractError::ReferrerAlreadySet.as_ref());
+            },
+            None => {
+                assert_eq!(account.stake_balance, 0, "{}", ContractError::ReferrerAfterStake.as_ref());
+                account.referrer = Some(referrer_id);
+                self.accounts.insert(account_id, &UpgradableAccount::from(account));
+            }
+        }
+    }
+
+    /**
+     * Reward of total stake since last global change, unstaking balance accrue at reduced rate if accrue_on_unstaking
+     * Lock boost, token boost and referral bonus are per account and not included
+     */
     pub(crate) fn internal_calculate_global_reward(&self) -> Balance {
-        let lasted_block = if self.paused {
-            self.paused_in_block
+        let diff_block = self.effective_diff_block(self.last_block_balance_change);
+        let mut reward: U256 = calculate_reward(self.total_stake_balance, self.config.reward_numerator, diff_block, 1, self.config.reward_denumerator);
+        if self.config.accrue_on_unstaking {
+            let unstake_reward: U256 = calculate_reward(self.total_unstake_balance, self.config.reward_numerator, diff_block, 1, self.config.reward_denumerator);
+            reward += unstake_reward * U256::from(self.config.reduced_rate_bp) / U256::from(BASIS_POINTS);
+        }
+        u256_to_balance(reward)
+    }
+
+    /**
+     * Fold global reward into pre_reward and move last change to current block
+     */
+    pub(crate) fn internal_settle_global_reward(&mut self) {
+        self.pre_reward += self.internal_calculate_global_reward();
+        self.last_block_balance_change = self.internal_accrual_clock();
+    }
+
+    /**
+     * Reward clock without paused blocks, it stop at pause block while contract is paused
+     * Accrual points (last change, reward start, config history) are in this clock, so past pauses are skipped without history
+     */
+    pub(crate) fn internal_accrual_clock(&self) -> u64 {
+        let clock: u64 = if self.paused {
+            std::cmp::min(self.paused_in_block, self.config.reward_clock())
         } else {
-            env::block_index()
+            self.config.reward_clock()
         };
-        let diff_block = lasted_block - self.last_block_balance_change;
-        let reward: U256 = (U256::from(self.total_stake_balance) * U256::from(self.config.reward_numerator) * U256::from(diff_block)) / U256::from(self.config.reward_denumerator);
-        reward.as_u128()
+        clock.saturating_sub(self.total_paused_blocks)
+    }
+
+    /**
+     * Number of blocks reward accrue from last change to current block, paused blocks are not counted
+     * Blocks before reward_start_block are not counted, no block counted if reward never funded
+     * With PerSecond time basis every block value here is unix seconds, so result is elapsed seconds
+     */
+    pub(crate) fn effective_diff_block(&self, last_change: BlockHeight) -> u64 {
+        match self.reward_start_block {
+            Some(reward_start_block) => self.internal_accrual_clock().saturating_sub(std::cmp::max(last_change, reward_start_block)),
+            None => 0
+        }
+    }
+
+    /**
+     * Owner transfer FT token to fund reward
+     * Reward start accrue from the first funding block
+     */
+    pub(crate) fn internal_fund_reward(&mut self, sender_id: AccountId, amount: Balance) {
+        assert_eq!(self.owner_id, sender_id, "{}", ContractError::OnlyOwnerContract.as_ref());
+
+        self.total_reward_fund += amount;
+        if self.reward_start_block.is_none() {
+            self.reward_start_block = Some(self.internal_accrual_clock());
+        }
+    }
+
+    /**
+     * Count reward leaving pool in current harvest window, reject if window limit is exceeded
+     * Window restart when harvest_window_blocks passed since its start
+     */
+    pub(crate) fn internal_consume_harvest_window(&mut self, amount: Balance) {
+        let max_harvest_per_window: Balance = match self.max_harvest_per_window {
+            Some(max_harvest_per_window) => max_harvest_per_window,
+            None => return
+        };
+
+        if env::block_index() >= self.harvest_window_start + self.harvest_window_blocks {
+            self.harvest_window_start = env::block_index();
+            self.harvested_in_window = 0;
+        }
+        assert!(self.harvested_in_window + amount <= max_harvest_per_window, "{}", ContractError::PoolHarvestThrottled.as_ref());
+
+        self.harvested_in_window += amount;
+    }
+
+    /**
+     * FT balance of contract tracked from deposits, reward fund and payouts
+     */
+    pub(crate) fn internal_tracked_holdings(&self) -> Balance {
+        self.total_stake_balance + self.total_unstake_balance + self.total_reward_fund - self.total_paid_reward_balance
+    }
+
+    /**
+     * Count outgoing transfer of staking FT until its callback, fee token deposit can't measure balance meanwhile
+     */
+    pub(crate) fn internal_start_ft_transfer(&mut self) {
+        self.pending_ft_transfers += 1;
+        self.ft_transfer_nonce += 1;
+    }
+
+    pub(crate) fn internal_end_ft_transfer(&mut self) {
+        self.pending_ft_transfers = self.pending_ft_transfers.saturating_sub(1);
+    }
+
+    /**
+     * Reject harvest, compound or unregister with reward before harvest_cooldown_blocks since last harvest
+     */
+    pub(crate) fn assert_harvest_cooldown(&self, account: &Account) {
+        if let Some(harvest_cooldown_blocks) = self.config.harvest_cooldown_blocks {
+            assert!(
+                account.last_harvest_block == 0 || env::block_index() >= account.last_harvest_block + harvest_cooldown_blocks,
+                "{}", ContractError::HarvestCooldown.as_ref()
+            );
+        }
+    }
+
+    /**
+     * Reject transfer out early if estimated FT balance can't cover it, so transfer doesn't fail after user paid gas
+     * Estimated balance is tracked holdings minus shortfall found by last sync_reward_reserve
+     */
+    pub(crate) fn assert_contract_balance_covers(&self, amount: Balance) {
+        let estimated_balance: Balance = self.internal_tracked_holdings().saturating_sub(self.ft_balance_shortfall);
+        assert!(amount <= estimated_balance, "{}", ContractError::InsufficientContractBalance.as_ref());
+    }
+
+    /**
+     * Assert contract FT holdings still cover stake, unstake and outstanding reward after outgoing amount
+     * Holdings is tracked from deposits, reward fund and payouts; outstanding reward is accrued but not paid, fee is not owed
+     * Global accrual is scaled by max account boost, so boosted and referral reward are covered
+     */
+    pub(crate) fn assert_solvent_after(&self, outgoing: Balance) {
+        let holdings: Balance = self.internal_tracked_holdings();
+        let accrued_reward: Balance = u256_to_balance(
+            U256::from(self.pre_reward + self.internal_calculate_global_reward()) * U256::from(self.internal_max_reward_boost()) / U256::from(BASIS_POINTS)
+        );
+        let outstanding_reward: Balance = accrued_reward.saturating_sub(self.total_paid_reward_balance + self.total_fee_collected);
+        let required: Balance = self.total_stake_balance + self.total_unstake_balance + outstanding_reward;
+
+        assert!(
+            holdings.checked_sub(outgoing).map_or(false, |remaining| remaining >= required),
+            "{}", ContractError::Insolvent.as_ref()
+        );
+    }
+
+    /**
+     * Max account reward over global accrual in basis points: top lock tier, max token boost and referral bonus
+     */
+    pub(crate) fn internal_max_reward_boost(&self) -> Balance {
+        let lock_boost: Balance = self.lock_tiers
+            .iter()
+            .map(|tier| Balance::from(tier.boost_basis_points))
+            .max()
+            .unwrap_or_else(|| Balance::from(BASIS_POINTS));
+
+        lock_boost * (Balance::from(BASIS_POINTS) + Balance::from(self.config.max_token_boost_bp)) / Balance::from(BASIS_POINTS)
+            * (Balance::from(BASIS_POINTS) + Balance::from(self.config.referral_bonus_bp)) / Balance::from(BASIS_POINTS)
+    }
+
+    /**
+     * Reward boost of account lock tier in basis points, no lock or expired lock is 1x
+     * Boost only apply for account reward, global reward don't include boost
+     */
+    pub(crate) fn internal_lock_boost(&self, account: &Account) -> u32 {
+        if env::epoch_height() >= account.lock_until_epoch {
+            return BASIS_POINTS;
+        }
+
+        account.lock_tier
+            .and_then(|tier_index| self.lock_tiers.get(tier_index as usize))
+            .map(|tier| tier.boost_basis_points)
+            .unwrap_or(BASIS_POINTS)
+    }
+
+    /**
+     * Extra reward weight of boost token in basis points
+     * Proportional to boost balance over stake balance, capped by max_token_boost_bp
+     */
+    pub(crate) fn internal_token_boost(&self, account: &Account) -> u32 {
+        if account.stake_balance == 0 || account.boost_balance == 0 {
+            return 0;
+        }
+
+        let token_boost: U256 = U256::from(account.boost_balance) * U256::from(BASIS_POINTS) / U256::from(account.stake_balance);
+        std::cmp::min(token_boost, U256::from(self.config.max_token_boost_bp)).as_u32()
+    }
+
+    /**
+     * User deposit boost token to raise reward weight, settle reward with old weight first
+     */
+    pub(crate) fn internal_deposit_boost(&mut self, account_id: AccountId, amount: Balance) {
+        assert!(!self.paused, "{}", ContractError::ContractPaused.as_ref());
+        assert_eq!(self.boost_ft_contract_id.as_ref(), Some(&env::predecessor_account_id()), "{}", ContractError::NotValidFtContract.as_ref());
+        let mut account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+
+        self.internal_settle_account_reward(&mut account);
+        account.boost_balance += amount;
+
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+    }
+
+    /**
+     * Share of stake balance in pool in basis points, 0 if pool is empty
+     */
+    pub(crate) fn internal_pool_share_basis_points(&self, stake_balance: Balance) -> u32 {
+        if self.total_stake_balance == 0 {
+            return 0;
+        }
+
+        (U256::from(stake_balance) * U256::from(BASIS_POINTS) / U256::from(self.total_stake_balance)).as_u32()
+    }
+
+    /**
+     * Drop config history entries ended before checkpoint, every account must be settled at or after checkpoint
+     * Entry in effect at checkpoint is kept as oldest entry
+     */
+    pub(crate) fn internal_prune_config_history(&mut self, checkpoint: BlockHeight) {
+        let mut first: u64 = 0;
+        while first + 1 < self.config_history.len() && self.config_history.get(first + 1).unwrap().0 <= checkpoint {
+            first += 1;
+        }
+        if first == 0 {
+            return;
+        }
+
+        let kept: Vec<(BlockHeight, Config)> = (first..self.config_history.len())
+            .map(|index| self.config_history.get(index).unwrap())
+            .collect();
+        self.config_history.clear();
+        for entry in kept.iter() {
+            self.config_history.push(entry);
+        }
+
+        emit_event("prune_config_history", json!({
+            "checkpoint": checkpoint,
+            "removed": first
+        }));
     }
 
+    /**
+     * Remove account and refund storage cost it used to account owner
+     */
+    pub(crate) fn internal_remove_account(&mut self, account_id: AccountId) {
+        let before_storage_usage = env::storage_usage();
+        self.accounts.remove(&account_id);
+        // Remove move last account to removed index, it may be skipped by running settle sweep
+        self.settle_sweep_next_index = 0;
+        let after_storage_usage = env::storage_usage();
+
+        let refund: Balance = env::storage_byte_cost() * Balance::from(before_storage_usage - after_storage_usage);
+        if refund > 0 {
+            Promise::new(account_id.clone()).transfer(refund);
+        }
+
+        emit_event("storage_unregister", json!({
+            "account_id": account_id,
+            "storage_refund": U128(refund)
+        }));
+    }
+
+    /**
+     * Read account in mutating context, account stored as Default variant is converted and re-persisted as Current
+     */
+    pub(crate) fn internal_get_account(&mut self, account_id: &AccountId) -> Option<Account> {
+        match self.accounts.get(account_id)? {
+            UpgradableAccount::Default(account) => {
+                let account: Account = Account::from(account);
+                self.accounts.insert(account_id, &UpgradableAccount::Current(account.clone()));
+                Some(account)
+            },
+            UpgradableAccount::Current(account) => Some(account)
+        }
+    }
+
+    /**
+     * Register new account, reject if number of accounts reached max_accounts
+     */
     pub(crate) fn internal_create_account(&mut self, account: AccountId) {
+        if let Some(max_accounts) = self.config.max_accounts {
+            assert!(self.accounts.len() < max_accounts, "{}", ContractError::MaxAccounts.as_ref());
+        }
+
         let new_account = Account {
             stake_balance: 0,
-            pre_stake_balance: 0,
             pre_reward: 0,
-            last_block_balance_change: env::block_index(),
+            last_block_balance_change: self.internal_accrual_clock(),
             unstake_balance: 0,
             unstake_available_epoch_height: 0,
-            unstake_start_timestamp: 0
+            unstake_start_timestamp: 0,
+            lock_tier: None,
+            lock_until_epoch: 0,
+            locked_balance: 0,
+            frozen: false,
+            reward_remainder: 0,
+            last_harvest_block: 0,
+            unwrap_on_harvest: false,
+            referrer: None,
+            boost_balance: 0,
+            operation_in_flight: false,
+            compound_count: 0,
+            last_compound_block: 0,
+            vesting_balance: 0,
+            vested_balance: 0,
+            vesting_start_block: 0,
+            vesting_end_block: 0
         };
 
         let upgrade_account = UpgradableAccount::from(new_account);
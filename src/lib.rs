@@ -1,4 +1,4 @@
-use near_sdk::collections::LookupMap;
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::{near_bindgen, AccountId, env, PanicOnDefault, Balance, EpochHeight, BlockHeight, BorshStorageKey, Promise, PromiseResult, PromiseOrValue, ext_contract};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
@@ -11,39 +11,189 @@ construct_uint! {
 }
 
 use crate::account::*;
-pub use crate::enumeration::PoolInfo;
-pub use crate::account::AccountJson;
+pub use crate::enumeration::{PoolInfo, PoolInfoHuman, ContractMetadata, Dashboard, UnstakeStatus, UnstakeTranche, RewardBreakdown, TimeConfig, AccrualStatus, RewardParams, RewardTrace};
+pub use crate::account::{AccountJson, AccountDebug};
+pub use crate::error::ContractError;
+pub use crate::core_impl::{TransferResult, TransferKind, TransferAction};
 use crate::util::*;
+use crate::event::*;
 
 mod account;
 mod util;
 mod internal;
 mod core_impl;
 mod enumeration;
+mod error;
+mod owner;
+mod event;
 
 pub const NO_DEPOSIT: Balance = 0;
 pub const DEPOSIT_ONE_YOCTOR: Balance = 1;
 pub const NUM_EPOCHS_TO_UNLOCK: EpochHeight = 1;
+pub const BASIS_POINTS: u32 = 10_000;
+pub const BLOCKS_PER_YEAR: u64 = 21_024_000; // Average 1.5 seconds per block
+pub const MAX_BATCH: usize = 100; // Max length of Vec input, keep batch methods under gas limit
+pub const DEFAULT_EPOCH_DURATION_SECONDS: u64 = 43_200; // Average 12 hours per epoch
+pub const DEFAULT_BLOCKS_PER_EPOCH: u64 = 43_200;
+pub const DEFAULT_HARVEST_MEMO: &str = "Staking contract harvest";
+pub const DEFAULT_WITHDRAW_MEMO: &str = "Staking contract withdraw";
+pub const MAX_FORECAST_BLOCKS: BlockHeight = BLOCKS_PER_YEAR; // Max lookahead of forecast views
+pub const MAX_FORECAST_EPOCHS: EpochHeight = 730; // One year of 12 hours epochs
+pub const APR_TOLERANCE_BASIS_POINTS: u32 = 100; // Max gap between declared total_apr and derived APR
+pub const DEFAULT_HARVEST_WINDOW_BLOCKS: BlockHeight = 43_200; // One epoch
+pub const SECONDS_PER_YEAR: u64 = 31_536_000;
+pub const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+pub const COMPOUND_PRECISION: u128 = 1_000_000_000_000_000_000; // Fixed point scale of compounded APY math
+pub const MAX_CONFIG_HISTORY: u64 = 50; // Max config history entries walked by account settlement
 
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy)]
+/**
+ * Unit reward accrue per, reward_numerator / reward_denumerator is rate of one unit
+ * PerSecond give same wall-clock APR regardless of block time
+ */
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum TimeBasis {
+    PerBlock,
+    PerSecond
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, Debug)]
 #[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
 pub struct Config {
     // Percent reward per 1 block
     pub reward_numerator: u32,
     pub reward_denumerator: u64,
-    pub total_apr: u32
+    pub total_apr: u32,
+    pub min_staking_amount: U128, // Min amount of each deposit
+    // Scale reward before division so reward smaller than 1 unit is carried instead of floored.
+    // Bigger multiplier keep more precision for small stake of low decimals token, but make
+    // intermediate U256 product bigger and each account store remainder in scaled unit, so it is fixed after init
+    pub reward_precision_multiplier: u64,
+    pub max_stake_per_account: Option<U128>, // Max stake balance of one account, deposit over cap is refunded
+    pub harvest_cooldown_blocks: Option<BlockHeight>, // Min blocks between two harvests of one account
+    pub min_unstake_amount: U128, // Min amount of each unstake, except unstake all remaining balance
+    pub apr_basis_points: Option<u32>, // Declared APR in basis points, None for legacy config only have total_apr percent
+    pub referral_bonus_bp: u32, // Part of referee settled reward credited to referrer, in basis points
+    pub harvest_fee_basis_points: u32, // Fee taken from harvested reward
+    pub epoch_duration_seconds: Option<u64>, // Estimate of epoch duration for countdown, None is DEFAULT_EPOCH_DURATION_SECONDS
+    pub accrue_on_unstaking: bool, // Unstaked balance waiting for withdraw keep accrue reward at reduced rate
+    pub reduced_rate_bp: u32, // Reward rate of unstaking balance in basis points of stake rate
+    pub estimated_blocks_per_epoch: Option<u64>, // Estimate to convert epoch lock to blocks, None is DEFAULT_BLOCKS_PER_EPOCH
+    pub max_token_boost_bp: u32, // Max extra reward weight from boost token in basis points, 0 disable boost
+    pub max_accrued_reward_per_account: Option<U128>, // Account stop accrue when unharvested reward reach this cap
+    pub registration_fee: U128, // Flat NEAR fee on top of storage cost when register new account, kept by contract
+    pub time_basis: TimeBasis, // PerSecond store unix seconds in reward accrual block fields (last_block_balance_change, ...)
+    pub max_accounts: Option<u64>, // Max registered accounts, bound storage of contract, None is unlimited
+    pub permissioned_registration: bool, // Only owner can register new account, for KYC pools
+    pub vesting_blocks: Option<BlockHeight>, // Harvested reward vest linearly over this many blocks and is paid by claim_vested, None pay on harvest
+    pub fee_on_transfer_token: bool // FT charge transfer fee, stake and fund reward credit FT balance increase instead of transferred amount
 }
 
 impl Default for Config {
     fn default() -> Self {
-        // By default APR 15%
-        Self { reward_numerator: 715, reward_denumerator: 100000000000, total_apr: 15 }
+        // By default APR 15%: 715 / 10^11 per block * BLOCKS_PER_YEAR ~ 15.03%
+        Self {
+            reward_numerator: 715,
+            reward_denumerator: 100000000000,
+            total_apr: 15,
+            min_staking_amount: U128(0),
+            reward_precision_multiplier: 1,
+            max_stake_per_account: None,
+            harvest_cooldown_blocks: None,
+            min_unstake_amount: U128(0),
+            apr_basis_points: Some(1500),
+            referral_bonus_bp: 0,
+            harvest_fee_basis_points: 0,
+            epoch_duration_seconds: None,
+            accrue_on_unstaking: false,
+            reduced_rate_bp: 0,
+            estimated_blocks_per_epoch: None,
+            max_token_boost_bp: 0,
+            max_accrued_reward_per_account: None,
+            registration_fee: U128(0),
+            time_basis: TimeBasis::PerBlock,
+            max_accounts: None,
+            permissioned_registration: false,
+            vesting_blocks: None,
+            fee_on_transfer_token: false
+        }
+    }
+}
+
+impl Config {
+    pub fn assert_valid(&self) {
+        assert!(self.reward_denumerator > 0, "{}", ContractError::InvalidConfig.as_ref());
+        assert!(self.reward_precision_multiplier > 0, "{}", ContractError::InvalidConfig.as_ref());
+    }
+
+    /**
+     * Effective APR in basis points derived from reward numerator and denumerator
+     */
+    pub fn effective_apr_basis_points(&self) -> u32 {
+        let apr: U256 = U256::from(self.reward_numerator) * U256::from(self.units_per_year()) * U256::from(BASIS_POINTS) / U256::from(self.reward_denumerator);
+        apr.as_u32()
+    }
+
+    /**
+     * Number of accrual units in one year, blocks or seconds by time_basis
+     */
+    pub fn units_per_year(&self) -> u64 {
+        match self.time_basis {
+            TimeBasis::PerBlock => BLOCKS_PER_YEAR,
+            TimeBasis::PerSecond => SECONDS_PER_YEAR
+        }
+    }
+
+    /**
+     * Accrual units of number of blocks, seconds are estimated from BLOCKS_PER_YEAR
+     */
+    pub fn blocks_to_units(&self, blocks: u64) -> u64 {
+        match self.time_basis {
+            TimeBasis::PerBlock => blocks,
+            TimeBasis::PerSecond => (U256::from(blocks) * U256::from(SECONDS_PER_YEAR) / U256::from(BLOCKS_PER_YEAR)).as_u64()
+        }
+    }
+
+    /**
+     * Number of blocks covering accrual units, rounded up
+     */
+    pub fn units_to_blocks(&self, units: u64) -> u64 {
+        match self.time_basis {
+            TimeBasis::PerBlock => units,
+            TimeBasis::PerSecond => {
+                let blocks: U256 = (U256::from(units) * U256::from(BLOCKS_PER_YEAR) + U256::from(SECONDS_PER_YEAR - 1)) / U256::from(SECONDS_PER_YEAR);
+                blocks.min(U256::from(u64::MAX)).as_u64()
+            }
+        }
+    }
+
+    /**
+     * Current point of reward accrual, block height or unix seconds by time_basis
+     */
+    pub fn reward_clock(&self) -> u64 {
+        match self.time_basis {
+            TimeBasis::PerBlock => env::block_index(),
+            TimeBasis::PerSecond => env::block_timestamp() / NANOSECONDS_PER_SECOND
+        }
     }
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(feature = "camel_case_api", serde(rename_all = "camelCase"))]
+pub struct LockTier {
+    pub lock_epochs: EpochHeight, // Number of epochs stake is locked
+    pub boost_basis_points: u32 // Reward multiplier, 10000 is 1x
+}
+
 #[derive(BorshDeserialize, BorshSerialize, BorshStorageKey)]
 pub enum StorageKey {
-    AccountKey
+    AccountKey,
+    ConfigHistoryKey,
+    AccountIdsKey,
+    HarvestReceiverAllowlistKey,
+    AccountMapKey
 }
 
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
@@ -51,44 +201,124 @@ pub enum StorageKey {
 pub struct StakingContract {
     pub owner_id: AccountId, // Owner of contract
     pub ft_contract_id: AccountId,
+    pub ft_decimals: u8, // Decimals of staking FT token
     pub config: Config, // Config reward and apr for contract
     pub total_stake_balance: Balance, // Total token balance lock in contract
+    pub total_unstake_balance: Balance, // Total token unstaked and waiting for withdraw
     pub total_paid_reward_balance: Balance,
+    pub total_fee_collected: Balance, // Total harvest fee kept by contract
     pub total_staker: Balance,
     pub pre_reward: Balance, // Pre reward before change total balance
-    pub last_block_balance_change: BlockHeight,
-    pub accounts: LookupMap<AccountId, UpgradableAccount>, // List staking user
+    pub last_block_balance_change: BlockHeight, // In accrual clock, unix seconds if config time_basis is PerSecond
+    pub accounts: UnorderedMap<AccountId, UpgradableAccount>, // List staking user, iterable for enumeration
     pub paused: bool, // Pause staking pool with limit reward,
-    pub paused_in_block: BlockHeight
+    pub paused_in_block: BlockHeight,
+    pub total_reward_fund: Balance, // Total reward token funded by owner
+    pub reward_start_block: Option<BlockHeight>, // Block reward start accrue, set when owner first fund reward
+    pub lock_tiers: Vec<LockTier>, // Lock tiers user can choose when stake
+    pub config_history: Vector<(BlockHeight, Config)>, // Config and accrual clock it take effect, split accrual of accounts not settled since
+    pub wrap_near_contract_id: Option<AccountId>, // Wrap NEAR contract, used to unwrap reward on harvest
+    pub harvest_receiver_allowlist: UnorderedSet<AccountId>, // Receivers allowed in harvest_to
+    pub harvest_receiver_allowlist_enabled: bool,
+    pub boost_ft_contract_id: Option<AccountId>, // Token user deposit to boost reward weight
+    pub harvest_memo: String, // Memo of harvest ft_transfer, for reconciliation of integrations
+    pub withdraw_memo: String, // Memo of withdraw ft_transfer
+    pub strict_config: bool, // Reject config with total_apr not matching reward numerator and denumerator
+    pub max_harvest_per_window: Option<Balance>, // Max reward harvested by all accounts in one window, None is unlimited
+    pub harvest_window_blocks: BlockHeight,
+    pub harvest_window_start: BlockHeight, // Start block of current harvest window
+    pub harvested_in_window: Balance, // Reward harvested in current window, failed transfer still count
+    pub ft_balance_shortfall: Balance, // Stake and unstake balance not covered by FT balance at last sync_reward_reserve
+    pub treasury_id: Option<AccountId>, // Receive harvest fee and slashed token, None keep them in reward reserve
+    pub total_registration_fee: Balance, // NEAR registration fee collected and not withdrawn by owner
+    pub total_paused_blocks: u64, // Blocks of past pauses, accrual clock is reward clock minus this
+    pub pending_ft_transfers: u64, // Outgoing transfers of staking FT waiting for callback
+    pub ft_transfer_nonce: u64, // Number of outgoing transfers of staking FT ever started
+    pub fee_deposit_in_flight: bool, // Fee token deposit waiting for balance check, other fee token deposits are refunded
+    pub total_transfer_fee_deducted: Balance, // Fee token amount sent by depositors but never received by contract
+    pub settle_sweep_start: BlockHeight, // Accrual clock when current owner_settle_all sweep started from index 0
+    pub settle_sweep_next_index: u64 // Next account index of current sweep, sweep reaching end prune config history
 }
 
 #[near_bindgen]
 impl StakingContract {
 
     #[init]
-    pub fn new_default_config(owner_id: AccountId, ft_contract_id: AccountId) -> Self {
-        Self::new(owner_id, ft_contract_id, Config::default())
+    pub fn new_default_config(owner_id: AccountId, ft_contract_id: AccountId, ft_decimals: u8) -> Self {
+        // By default min staking amount is 1 token
+        let config = Config {
+            min_staking_amount: U128(10u128.pow(ft_decimals as u32)),
+            ..Config::default()
+        };
+        Self::new(owner_id, ft_contract_id, config, ft_decimals, None)
     }
 
+    /**
+     * strict_init reject owner, FT contract and this contract being same account, off by default
+     * strict_init also reject config with total_apr not matching reward rate, kept for later update_config
+     * Panic if contract is already initialized, new_default_config go through here too
+     */
     #[init]
-    pub fn new(owner_id: AccountId, ft_contract_id: AccountId, config: Config) -> Self {
+    pub fn new(owner_id: AccountId, ft_contract_id: AccountId, config: Config, ft_decimals: u8, strict_init: Option<bool>) -> Self {
+        assert_not_initialized();
+        config.assert_valid();
+        let strict_config: bool = strict_init.unwrap_or(false);
+        check_apr_consistency(&config, strict_config);
+        if strict_config {
+            let current_account_id: AccountId = env::current_account_id();
+            assert!(
+                owner_id != ft_contract_id && ft_contract_id != current_account_id && owner_id != current_account_id,
+                "{}", ContractError::InvalidInitAccounts.as_ref()
+            );
+        }
+        let mut config_history = Vector::new(StorageKey::ConfigHistoryKey);
+        config_history.push(&(config.reward_clock(), config));
+
         StakingContract {
             owner_id,
             ft_contract_id,
+            ft_decimals,
             config,
             total_stake_balance: 0,
+            total_unstake_balance: 0,
             total_paid_reward_balance: 0,
+            total_fee_collected: 0,
             total_staker: 0,
             pre_reward: 0,
-            last_block_balance_change: env::block_index(),
-            accounts: LookupMap::new(StorageKey::AccountKey),
+            last_block_balance_change: config.reward_clock(),
+            accounts: UnorderedMap::new(StorageKey::AccountMapKey),
             paused: false,
-            paused_in_block: 0
+            paused_in_block: 0,
+            total_reward_fund: 0,
+            reward_start_block: None,
+            lock_tiers: Vec::new(),
+            config_history,
+            wrap_near_contract_id: None,
+            harvest_receiver_allowlist: UnorderedSet::new(StorageKey::HarvestReceiverAllowlistKey),
+            harvest_receiver_allowlist_enabled: false,
+            boost_ft_contract_id: None,
+            harvest_memo: DEFAULT_HARVEST_MEMO.to_string(),
+            withdraw_memo: DEFAULT_WITHDRAW_MEMO.to_string(),
+            strict_config,
+            max_harvest_per_window: None,
+            harvest_window_blocks: DEFAULT_HARVEST_WINDOW_BLOCKS,
+            harvest_window_start: env::block_index(),
+            harvested_in_window: 0,
+            ft_balance_shortfall: 0,
+            treasury_id: None,
+            total_registration_fee: 0,
+            total_paused_blocks: 0,
+            pending_ft_transfers: 0,
+            ft_transfer_nonce: 0,
+            fee_deposit_in_flight: false,
+            total_transfer_fee_deducted: 0,
+            settle_sweep_start: 0,
+            settle_sweep_next_index: 0
         }
     }
 
     pub fn get_total_pending_reward(&self) -> U128 {
-        assert_eq!(self.owner_id, env::predecessor_account_id(), "ERR_ONLY_OWNER_CONTRACT");
+        assert_eq!(self.owner_id, env::predecessor_account_id(), "{}", ContractError::OnlyOwnerContract.as_ref());
         U128(self.pre_reward + self.internal_calculate_global_reward())
     }
 
@@ -98,6 +328,7 @@ impl StakingContract {
 
     #[payable]
     pub fn storage_deposit(&mut self, account_id: Option<AccountId>) {
+        // Require deposit so function call access key can't register, storage cost is checked in refund_deposit
         assert_at_least_one_yocto();
         let account = account_id.unwrap_or_else(|| env::predecessor_account_id());
 
@@ -105,12 +336,70 @@ impl StakingContract {
         if account_stake.is_some() {
             refund_deposit(0);
         } else {
+            self.assert_registration_allowed();
             let before_storage_usage = env::storage_usage();
             self.internal_create_account(account.clone());
             let after_storage_usage = env::storage_usage();
 
-            refund_deposit(after_storage_usage - before_storage_usage);
+            let registration_fee: Balance = self.config.registration_fee.0;
+            refund_deposit_with_fee(after_storage_usage - before_storage_usage, registration_fee);
+            self.total_registration_fee += registration_fee;
+        }
+    }
+
+    /**
+     * Register many accounts in one call, already registered accounts are skipped
+     * Refund deposit not used for storage to caller
+     */
+    #[payable]
+    pub fn storage_deposit_bulk(&mut self, account_ids: Vec<AccountId>) {
+        assert_at_least_one_yocto();
+        assert_batch_len(account_ids.len());
+        self.assert_registration_allowed();
+
+        let before_storage_usage = env::storage_usage();
+        let mut new_accounts: u64 = 0;
+        for account_id in account_ids {
+            if self.accounts.get(&account_id).is_none() {
+                self.internal_create_account(account_id);
+                new_accounts += 1;
+            }
+        }
+        let after_storage_usage = env::storage_usage();
+
+        let registration_fee: Balance = self.config.registration_fee.0 * Balance::from(new_accounts);
+        refund_deposit_with_fee(after_storage_usage - before_storage_usage, registration_fee);
+        self.total_registration_fee += registration_fee;
+    }
+
+    /**
+     * Remove account with no stake, unstake, boost and vesting balance, storage cost is refunded
+     * Account with residual reward need force, reward is transferred first and account is removed in callback
+     * Account is kept if reward transfer fail
+     */
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> PromiseOrValue<bool> {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        let account: Account = match self.accounts.get(&account_id) {
+            Some(upgradable_account) => Account::from(upgradable_account),
+            None => return PromiseOrValue::Value(false)
+        };
+        assert!(
+            account.stake_balance == 0 && account.unstake_balance == 0 && account.boost_balance == 0
+                && account.vesting_balance == 0 && account.vested_balance == 0,
+            "{}", ContractError::AccountNotEmpty.as_ref()
+        );
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+
+        let reward: Balance = account.pre_reward + self.internal_calculate_account_reward(&account);
+        if reward == 0 {
+            self.internal_remove_account(account_id);
+            return PromiseOrValue::Value(true);
         }
+
+        assert!(force.unwrap_or(false), "{}", ContractError::RewardNotHarvested.as_ref());
+        PromiseOrValue::Promise(self.internal_unregister_with_reward(account_id, account, reward))
     }
 
     // View func get storage balance, return 0 if account need deposit to interact
@@ -123,24 +412,132 @@ impl StakingContract {
         }
     }
 
+    // View func check account registered, independent of stake balance
+    pub fn is_registered(&self, account_id: AccountId) -> bool {
+        self.accounts.get(&account_id).is_some()
+    }
+
     pub(crate) fn assert_owner(&self) {
         assert_eq!(env::predecessor_account_id(), self.owner_id, "Only owner contract can be access");
     }
 
+    /**
+     * New account can be registered by anyone, or only by owner if permissioned_registration is on
+     */
+    pub(crate) fn assert_registration_allowed(&self) {
+        if self.config.permissioned_registration {
+            assert_eq!(env::predecessor_account_id(), self.owner_id, "{}", ContractError::OnlyOwnerContract.as_ref());
+        }
+    }
+
+    /**
+     * Migrate state of baseline contract, move accounts from old LookupMap to UnorderedMap
+     * LookupMap can't be iterated, operator must supply every known account id (ex: from indexer of stake events)
+     * Account not in list is lost, list must fit gas of one call
+     * Baseline accrue reward from deploy and don't track reward fund, owner call sync_reward_reserve after migrate
+     */
     #[init(ignore_state)]
     #[private]
-    pub fn migrate() -> Self {
-        let contract: StakingContract = env::state_read().expect("ERR_READ_CONTRACT_STATE");
-        contract
+    pub fn migrate(account_ids: Vec<AccountId>, ft_decimals: u8) -> Self {
+        let mut old_contract: OldStakingContract = env::state_read().expect(ContractError::ReadContractState.as_ref());
+        let config: Config = Config {
+            reward_numerator: old_contract.config.reward_numerator,
+            reward_denumerator: old_contract.config.reward_denumerator,
+            total_apr: old_contract.config.total_apr,
+            ..Config::default()
+        };
+
+        let mut accounts: UnorderedMap<AccountId, UpgradableAccount> = UnorderedMap::new(StorageKey::AccountMapKey);
+        let mut total_unstake_balance: Balance = 0;
+        for account_id in account_ids {
+            if let Some(account) = old_contract.accounts.remove(&account_id) {
+                let account: AccountV1 = AccountV1::from(account);
+                total_unstake_balance += account.unstake_balance;
+                accounts.insert(&account_id, &UpgradableAccount::Default(account));
+            }
+        }
+
+        let mut config_history = Vector::new(StorageKey::ConfigHistoryKey);
+        config_history.push(&(config.reward_clock(), config));
+
+        StakingContract {
+            owner_id: old_contract.owner_id,
+            ft_contract_id: old_contract.ft_contract_id,
+            ft_decimals,
+            config,
+            total_stake_balance: old_contract.total_stake_balance,
+            total_unstake_balance,
+            total_paid_reward_balance: old_contract.total_paid_reward_balance,
+            total_fee_collected: 0,
+            total_staker: old_contract.total_staker,
+            pre_reward: old_contract.pre_reward,
+            last_block_balance_change: old_contract.last_block_balance_change,
+            accounts,
+            paused: old_contract.paused,
+            paused_in_block: old_contract.paused_in_block,
+            total_reward_fund: old_contract.total_paid_reward_balance,
+            reward_start_block: Some(0),
+            lock_tiers: Vec::new(),
+            config_history,
+            wrap_near_contract_id: None,
+            harvest_receiver_allowlist: UnorderedSet::new(StorageKey::HarvestReceiverAllowlistKey),
+            harvest_receiver_allowlist_enabled: false,
+            boost_ft_contract_id: None,
+            harvest_memo: DEFAULT_HARVEST_MEMO.to_string(),
+            withdraw_memo: DEFAULT_WITHDRAW_MEMO.to_string(),
+            strict_config: false,
+            max_harvest_per_window: None,
+            harvest_window_blocks: DEFAULT_HARVEST_WINDOW_BLOCKS,
+            harvest_window_start: env::block_index(),
+            harvested_in_window: 0,
+            ft_balance_shortfall: 0,
+            treasury_id: None,
+            total_registration_fee: 0,
+            total_paused_blocks: 0,
+            pending_ft_transfers: 0,
+            ft_transfer_nonce: 0,
+            fee_deposit_in_flight: false,
+            total_transfer_fee_deducted: 0,
+            settle_sweep_start: 0,
+            settle_sweep_next_index: 0
+        }
     }
 }
 
+/**
+ * Config layout of baseline contract, only used in migrate
+ */
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct OldConfig {
+    pub reward_numerator: u32,
+    pub reward_denumerator: u64,
+    pub total_apr: u32
+}
+
+/**
+ * State layout of baseline contract, only used in migrate
+ */
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldStakingContract {
+    pub owner_id: AccountId,
+    pub ft_contract_id: AccountId,
+    pub config: OldConfig,
+    pub total_stake_balance: Balance,
+    pub total_paid_reward_balance: Balance,
+    pub total_staker: Balance,
+    pub pre_reward: Balance,
+    pub last_block_balance_change: BlockHeight,
+    pub accounts: LookupMap<AccountId, OldUpgradableAccount>,
+    pub paused: bool,
+    pub paused_in_block: BlockHeight
+}
+
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
     use near_sdk::json_types::ValidAccountId;
-    use near_sdk::test_utils::{VMContextBuilder, accounts};
-    use near_sdk::{testing_env, MockedBlockchain};
+    use near_sdk::test_utils::{VMContextBuilder, accounts, get_logs};
+    use near_sdk::{testing_env, MockedBlockchain, VMConfig, RuntimeFeesConfig};
 
     fn get_context(is_view: bool) -> VMContextBuilder {
         let mut builder = VMContextBuilder::new();
@@ -158,7 +555,7 @@ mod tests {
         let context = get_context(false);
         testing_env!(context.build());
 
-        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), "ft_contract".to_string());
+        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), "ft_contract".to_string(), 0);
 
         assert_eq!(contract.owner_id, accounts(1).to_string(), "Contract owner should be equal {}", accounts(1).to_string());
         assert_eq!(contract.ft_contract_id, "ft_contract".to_string(), "FT contract id should be init data");
@@ -174,8 +571,28 @@ mod tests {
         let contract: StakingContract = StakingContract::new(accounts(1).to_string(), "ft_contract".to_string(), Config { 
             reward_numerator: 1500, 
             reward_denumerator: 10000000, 
-            total_apr: 15 
-        });
+            total_apr: 15,
+            min_staking_amount: U128(0),
+            reward_precision_multiplier: 1,
+            max_stake_per_account: None,
+            harvest_cooldown_blocks: None,
+            min_unstake_amount: U128(0),
+            apr_basis_points: Some(1500),
+            referral_bonus_bp: 0,
+            harvest_fee_basis_points: 0,
+            epoch_duration_seconds: None,
+            accrue_on_unstaking: false,
+            reduced_rate_bp: 0,
+            estimated_blocks_per_epoch: None,
+            max_token_boost_bp: 0,
+            max_accrued_reward_per_account: None,
+            registration_fee: U128(0),
+            time_basis: TimeBasis::PerBlock,
+            max_accounts: None,
+            permissioned_registration: false,
+            vesting_blocks: None,
+            fee_on_transfer_token: false
+        }, 24, None);
 
         assert_eq!(contract.owner_id, accounts(1).to_string(), "Contract owner should be equal {}", accounts(1).to_string());
         assert_eq!(contract.ft_contract_id, "ft_contract".to_string(), "FT contract id should be init data");
@@ -190,14 +607,15 @@ mod tests {
         context.block_index(0);
         testing_env!(context.build());
 
-        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string());
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
         contract.internal_create_account(env::predecessor_account_id());
 
         
         // Deposit and stake function call from FT contract
         context.predecessor_account_id(accounts(1));
         testing_env!(context.build());
-        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000);
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
 
         context.block_index(10);
         context.predecessor_account_id(accounts(0));
@@ -209,7 +627,6 @@ mod tests {
 
         assert_eq!(account.stake_balance, 10_000_000_000_000);
         assert_eq!(account.pre_reward, 0);
-        assert_eq!(account.pre_stake_balance, 0);
         assert!(contract.internal_calculate_account_reward(&account) > 0);
 
         // test contract balance
@@ -223,7 +640,7 @@ mod tests {
         // Deposit and stake function call from FT contract
         context.predecessor_account_id(accounts(1));
         testing_env!(context.build());
-        contract.internal_deposit_and_stake(accounts(0).to_string(), 20_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 20_000_000_000_000, None);
 
 
         context.block_index(20);
@@ -236,7 +653,6 @@ mod tests {
 
         assert_eq!(account_update.stake_balance, 30_000_000_000_000);
         assert!(account_update.pre_reward > 0);
-        assert_eq!(account_update.pre_stake_balance, 10_000_000_000_000);
         assert_eq!(account_update.last_block_balance_change, 10);
         assert!(contract.internal_calculate_account_reward(&account_update) > 0);
 
@@ -253,14 +669,14 @@ mod tests {
         context.block_index(0);
         testing_env!(context.build());
 
-        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string());
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
         contract.internal_create_account(env::predecessor_account_id());
 
         
         // Deposit and stake function call from FT contract
         context.predecessor_account_id(accounts(1));
         testing_env!(context.build());
-        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
 
         context.block_index(10);
         context.epoch_height(10);
@@ -283,4 +699,4292 @@ mod tests {
     fn withdraw_test() {
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn blocks_until_first_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(env::predecessor_account_id());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 1_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+
+        let blocks = contract.blocks_until_first_reward(accounts(0).to_string());
+        assert!(blocks > 0);
+        assert!(blocks < u64::MAX);
+
+        // One block before threshold reward still zero, at threshold reward accrue
+        context.block_index(blocks - 1);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 0);
+
+        context.block_index(blocks);
+        testing_env!(context.build());
+        assert!(contract.get_account_reward(accounts(0).to_string()).0 > 0);
+        assert_eq!(contract.blocks_until_first_reward(accounts(0).to_string()), 0);
+    }
+
+    #[test]
+    fn contract_error_message_test() {
+        assert_eq!(ContractError::NotFoundAccount.as_ref(), "ERR_NOT_FOUND_ACCOUNT");
+        assert_eq!(ContractError::ContractPaused.as_ref(), "ERR_CONTRACT_PAUSED");
+    }
+
+    #[test]
+    fn reward_start_after_funding_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(env::predecessor_account_id());
+
+        // Stake before owner fund reward
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 0);
+        assert_eq!(contract.internal_calculate_global_reward(), 0);
+
+        // Owner fund reward at block 100
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        assert_eq!(contract.reward_start_block, Some(100));
+        assert_eq!(contract.total_reward_fund, 1_000_000_000_000);
+
+        context.block_index(110);
+        testing_env!(context.build());
+
+        // Only 10 blocks after funding are counted
+        let config = Config::default();
+        let expected_reward = 10_000_000_000_000 * config.reward_numerator as u128 * 10 / config.reward_denumerator as u128;
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, expected_reward);
+        assert_eq!(contract.internal_calculate_global_reward(), expected_reward);
+
+        // Fund again don't move reward start block
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        assert_eq!(contract.reward_start_block, Some(100));
+    }
+
+    #[test]
+    fn init_with_decimals_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), "ft_contract".to_string(), 24);
+
+        assert_eq!(contract.ft_decimals, 24);
+        assert_eq!(contract.config.min_staking_amount, U128(1_000_000_000_000_000_000_000_000));
+
+        let metadata = contract.get_metadata();
+        assert_eq!(metadata.ft_decimals, 24);
+        assert_eq!(metadata.config.min_staking_amount, U128(1_000_000_000_000_000_000_000_000));
+    }
+
+    #[test]
+    fn stake_with_lock_tier_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_lock_tiers(vec![
+            LockTier { lock_epochs: 10, boost_basis_points: 12_000 },
+            LockTier { lock_epochs: 20, boost_basis_points: 15_000 }
+        ]);
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, Some(1));
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000_000_000_000, None);
+
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.lock_tier, Some(1));
+        assert_eq!(account.lock_until_epoch, 20);
+
+        context.block_index(100);
+        testing_env!(context.build());
+
+        // Locked account reward boosted 1.5x
+        let locked_reward = contract.get_account_reward(accounts(0).to_string()).0;
+        let unlocked_reward = contract.get_account_reward(accounts(2).to_string()).0;
+        assert!(unlocked_reward > 0);
+        assert_eq!(locked_reward, unlocked_reward * 15 / 10);
+    }
+
+    #[test]
+    fn lock_boost_locked_part_only_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        // Account 0 lock half of stake, account 2 no lock
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_lock_tiers(vec![LockTier { lock_epochs: 10, boost_basis_points: 15_000 }]);
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, Some(0));
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 20_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+
+        // Only locked half boosted 1.5x
+        let locked_reward = contract.get_account_reward(accounts(0).to_string()).0;
+        let unlocked_reward = contract.get_account_reward(accounts(2).to_string()).0;
+        assert!(unlocked_reward > 0);
+        assert_eq!(locked_reward, unlocked_reward * 125 / 100);
+
+        // Lock expired, no boost
+        context.epoch_height(10);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, unlocked_reward);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STAKE_LOCKED")]
+    fn unstake_locked_stake_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_lock_tiers(vec![LockTier { lock_epochs: 10, boost_basis_points: 12_000 }]);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, Some(0));
+
+        context.epoch_height(5);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+    }
+
+    #[test]
+    fn apr_history_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+
+        // Double reward rate
+        contract.update_config(Config {
+            reward_numerator: 1430,
+            total_apr: 30,
+            ..Config::default()
+        });
+
+        let apr_history = contract.get_apr_history(0, 10);
+        assert_eq!(apr_history.len(), 2);
+        assert_eq!(apr_history[0].0, 0);
+        assert_eq!(apr_history[1].0, 100);
+        assert_eq!(apr_history[0].1, Config::default().effective_apr_basis_points());
+        assert_eq!(apr_history[1].1, apr_history[0].1 * 2);
+
+        // Default config is ~15% APR
+        assert!(apr_history[0].1 >= 1450 && apr_history[0].1 <= 1550);
+
+        assert_eq!(contract.get_apr_history(1, 10).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CONFIG_HISTORY_FULL")]
+    fn config_history_full_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(1));
+        for block in 1..MAX_CONFIG_HISTORY {
+            context.block_index(block);
+            testing_env!(context.build());
+            contract.update_config(Config::default());
+        }
+        assert_eq!(contract.config_history.len(), MAX_CONFIG_HISTORY);
+
+        contract.update_config(Config::default());
+    }
+
+    #[test]
+    fn settle_sweep_prune_config_history_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000_000_000_000, None);
+
+        context.predecessor_account_id(accounts(1));
+        for block in 1..MAX_CONFIG_HISTORY {
+            context.block_index(block * 100);
+            testing_env!(context.build());
+            contract.update_config(Config::default());
+        }
+
+        // Partial sweep keep history
+        context.block_index(MAX_CONFIG_HISTORY * 100 + 50);
+        testing_env!(context.build());
+        contract.owner_settle_all(0, 1);
+        assert_eq!(contract.config_history.len(), MAX_CONFIG_HISTORY);
+
+        // Non contiguous batch don't complete sweep
+        contract.owner_settle_all(2, 1);
+        assert_eq!(contract.config_history.len(), MAX_CONFIG_HISTORY);
+
+        let reward_before = contract.get_account_reward(accounts(2).to_string());
+        contract.owner_settle_all(1, 1);
+        assert_eq!(contract.config_history.len(), 1);
+        assert_eq!(contract.config_history.get(0).unwrap().0, (MAX_CONFIG_HISTORY - 1) * 100);
+        assert_eq!(contract.get_account_reward(accounts(2).to_string()), reward_before);
+
+        // Pruned history accept new config
+        contract.update_config(Config::default());
+        assert_eq!(contract.config_history.len(), 2);
+    }
+
+    #[test]
+    fn unstake_available_stake_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        // Stake 10 with lock tier and 5 without lock
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_lock_tiers(vec![LockTier { lock_epochs: 10, boost_basis_points: 12_000 }]);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, Some(0));
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 5_000_000_000_000, None);
+
+        context.epoch_height(5);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+
+        let account_json = contract.get_account_info(accounts(0).to_string());
+        assert_eq!(account_json.available_to_unstake, U128(5_000_000_000_000));
+
+        // Unstake not locked part
+        contract.internal_unstake(accounts(0).to_string(), 5_000_000_000_000);
+        let account_json = contract.get_account_info(accounts(0).to_string());
+        assert_eq!(account_json.stake_balance, U128(10_000_000_000_000));
+        assert_eq!(account_json.available_to_unstake, U128(0));
+
+        // Lock expired, all stake available
+        context.epoch_height(10);
+        testing_env!(context.build());
+        let account_json = contract.get_account_info(accounts(0).to_string());
+        assert_eq!(account_json.available_to_unstake, U128(10_000_000_000_000));
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STAKE_LOCKED")]
+    fn unstake_more_than_available_stake_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_lock_tiers(vec![LockTier { lock_epochs: 10, boost_basis_points: 12_000 }]);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, Some(0));
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 5_000_000_000_000, None);
+
+        context.epoch_height(5);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 6_000_000_000_000);
+    }
+
+    #[test]
+    fn storage_deposit_bulk_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1_000_000_000_000_000_000_000_000);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(2).to_string());
+
+        let before_storage_usage = env::storage_usage();
+        contract.storage_deposit_bulk(vec![accounts(2).to_string(), accounts(3).to_string(), accounts(4).to_string(), accounts(5).to_string()]);
+        let storage_used = env::storage_usage() - before_storage_usage;
+
+        for index in 2..6 {
+            assert_eq!(contract.storage_balance_of(accounts(index).to_string()), U128(1));
+        }
+
+        // Refund all deposit except storage cost of three new accounts
+        let storage_cost = env::storage_byte_cost() * Balance::from(storage_used);
+        assert!(storage_used > 0);
+        assert_eq!(refund_deposit(storage_used), 1_000_000_000_000_000_000_000_000 - storage_cost);
+    }
+
+    #[test]
+    fn freeze_account_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000_000_000_000, None);
+
+        // Freeze account 0 at block 100
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.freeze_account(accounts(0).to_string());
+        let frozen_reward = contract.get_account_reward(accounts(0).to_string()).0;
+        let other_reward = contract.get_account_reward(accounts(2).to_string()).0;
+        assert!(frozen_reward > 0);
+
+        context.block_index(200);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, frozen_reward);
+        assert!(contract.get_account_reward(accounts(2).to_string()).0 > other_reward);
+
+        // Unfreeze, reward accrue again
+        contract.unfreeze_account(accounts(0).to_string());
+        context.block_index(300);
+        testing_env!(context.build());
+        assert!(contract.get_account_reward(accounts(0).to_string()).0 > frozen_reward);
+    }
+
+    #[test]
+    fn is_registered_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        assert!(contract.is_registered(accounts(0).to_string()));
+        assert!(!contract.is_registered(accounts(2).to_string()));
+    }
+
+    #[test]
+    fn harvest_failed_callback_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let reward = contract.get_account_reward(accounts(0).to_string()).0;
+        let account_before = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+
+        // Transfer reward failed
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let result = contract.ft_transfer_callback(U128(reward), accounts(0).to_string(), U128(0));
+
+        assert_eq!(result, TransferResult { account_id: accounts(0).to_string(), amount: U128(0), kind: TransferKind::Harvest });
+        assert_eq!(Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()), account_before);
+        assert_eq!(contract.total_paid_reward_balance, 0);
+        assert!(get_logs().iter().any(|log| log.starts_with("EVENT_JSON:") && log.contains("\"event\":\"harvest_failed\"")));
+    }
+
+    #[test]
+    fn reward_precision_multiplier_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        // 1000 stake earn 2.5 reward per block
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            reward_numerator: 1,
+            reward_denumerator: 400,
+            reward_precision_multiplier: 1_000_000,
+            ..Config::default()
+        }, 2, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 1_000, None);
+
+        // Settle every block, remainder is carried instead of floored
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        context.block_index(1);
+        testing_env!(context.build());
+        contract.internal_settle_account_reward(&mut account);
+        assert_eq!(account.pre_reward, 2);
+        assert_eq!(account.reward_remainder, 500_000);
+
+        context.block_index(2);
+        testing_env!(context.build());
+        contract.internal_settle_account_reward(&mut account);
+
+        // Floor each block give 4, carry remainder give 5
+        assert_eq!(account.pre_reward, 5);
+        assert_eq!(account.reward_remainder, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_CONFIG")]
+    fn update_config_precision_multiplier_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            reward_numerator: 1,
+            reward_denumerator: 400,
+            reward_precision_multiplier: 1_000_000,
+            ..Config::default()
+        }, 2, None);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.update_config(Config {
+            reward_numerator: 1,
+            reward_denumerator: 400,
+            reward_precision_multiplier: 1_000,
+            ..Config::default()
+        });
+    }
+
+    #[test]
+    fn pool_info_human_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 24);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 12_500_000_000_000_000_000_000_000, None);
+
+        let pool_info = contract.get_pool_info_human();
+        assert_eq!(pool_info.total_stake_balance, "12.5");
+        assert_eq!(pool_info.total_reward, "0");
+        assert_eq!(pool_info.total_stakers, U128(1));
+
+        assert_eq!(format_decimal(1, 24), "0.000000000000000000000001");
+        assert_eq!(format_decimal(3_000_000, 6), "3");
+    }
+
+    #[test]
+    fn max_stake_per_account_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            max_stake_per_account: Some(U128(15_000)),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        assert_eq!(contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000, None), 0);
+
+        // Only 5000 accepted, the rest is returned
+        assert_eq!(contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000, None), 5_000);
+        assert_eq!(contract.total_stake_balance, 15_000);
+
+        // Account at cap, all deposit returned
+        assert_eq!(contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000, None), 10_000);
+        assert_eq!(contract.total_stake_balance, 15_000);
+    }
+
+    #[test]
+    fn harvest_cooldown_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            harvest_cooldown_blocks: Some(100),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // First harvest at block 10
+        context.block_index(10);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.harvest();
+
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        let reward = contract.get_account_reward(accounts(0).to_string()).0;
+        contract.ft_transfer_callback(U128(reward), accounts(0).to_string(), U128(0));
+        assert_eq!(Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()).last_harvest_block, 10);
+
+        // Harvest after cooldown
+        context.block_index(110);
+        testing_env!(context.build());
+        contract.harvest();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_HARVEST_COOLDOWN")]
+    fn harvest_in_cooldown_test() {
+        let mut context = get_context(false);
+        context.block_index(10);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            harvest_cooldown_blocks: Some(100),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        account.pre_reward = 1_000;
+        account.last_harvest_block = 10;
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+
+        context.block_index(50);
+        testing_env!(context.build());
+        contract.harvest();
+    }
+
+    #[test]
+    fn withdraw_failed_rollback_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+        assert_eq!(contract.total_unstake_balance, 10_000_000_000_000);
+
+        context.epoch_height(5);
+        testing_env!(context.build());
+        let account_before = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        let total_stake_before = contract.total_stake_balance;
+        let total_staker_before = contract.total_staker;
+
+        let old_account = contract.internal_withdraw(accounts(0).to_string());
+        assert_eq!(contract.total_unstake_balance, 0);
+
+        // Transfer failed, rollback account and contract data
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(contract.ft_withdraw_callback(accounts(0).to_string(), old_account).amount, U128(0));
+
+        assert_eq!(Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()), account_before);
+        assert_eq!(contract.total_unstake_balance, 10_000_000_000_000);
+        assert_eq!(contract.total_stake_balance, total_stake_before);
+        assert_eq!(contract.total_staker, total_staker_before);
+    }
+
+    #[test]
+    fn withdraw_failed_keep_interim_changes_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        context.attached_deposit(10u128.pow(24));
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        context.epoch_height(5);
+        testing_env!(context.build());
+        let old_account = contract.internal_withdraw(accounts(0).to_string());
+
+        // Owner credit reward while withdraw transfer is pending
+        contract.owner_credit_reward(accounts(0).to_string(), U128(1_000));
+
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.ft_withdraw_callback(accounts(0).to_string(), old_account.clone());
+
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.pre_reward, old_account.pre_reward + 1_000);
+        assert_eq!(account.unstake_balance, 10_000_000_000_000);
+        assert_eq!(account.unstake_available_epoch_height, old_account.unstake_available_epoch_height);
+        assert!(!account.operation_in_flight);
+        assert_eq!(contract.total_unstake_balance, 10_000_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OPERATION_IN_FLIGHT")]
+    fn unstake_withdraw_in_flight_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        context.epoch_height(5);
+        testing_env!(context.build());
+        contract.internal_withdraw(accounts(0).to_string());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+    }
+
+    #[test]
+    fn min_unstake_amount_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            min_unstake_amount: U128(5_000),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 12_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 9_000);
+
+        // Unstake all remaining balance is allowed even below min
+        contract.internal_unstake(accounts(0).to_string(), 3_000);
+        assert_eq!(contract.total_stake_balance, 0);
+        assert_eq!(contract.total_unstake_balance, 12_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_LESS_THAN_MIN_UNSTAKE")]
+    fn unstake_below_min_amount_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            min_unstake_amount: U128(5_000),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 12_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 1_000);
+    }
+
+    #[test]
+    fn exit_below_min_unstake_no_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            min_unstake_amount: U128(5_000),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        // Available stake after lock is below min_unstake_amount, no reward is funded
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_lock_tiers(vec![LockTier { lock_epochs: 10, boost_basis_points: 15_000 }]);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000, Some(0));
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 1_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        let result = match contract.exit() {
+            PromiseOrValue::Value(result) => result,
+            PromiseOrValue::Promise(_) => panic!("harvest without reward")
+        };
+        assert_eq!(result.amount.0, 0);
+
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.stake_balance, 10_000);
+        assert_eq!(account.unstake_balance, 1_000);
+        assert!(!account.operation_in_flight);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_FOUND_ACCOUNT")]
+    fn exit_not_found_account_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.exit();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_FOUND_ACCOUNT")]
+    fn harvest_to_not_found_account_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.harvest_to(accounts(2).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_FOUND_ACCOUNT")]
+    fn claim_vested_not_found_account_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.claim_vested();
+    }
+
+    #[test]
+    fn recount_stakers_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+        contract.internal_create_account(accounts(3).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000, None);
+
+        assert_eq!(contract.get_number_of_accounts(), 3);
+        assert_eq!(contract.get_accounts(1, 10), vec![accounts(2).to_string(), accounts(3).to_string()]);
+
+        // Corrupt total staker
+        contract.total_staker = 5;
+        assert_eq!(contract.recount_stakers(), 2);
+    }
+
+    #[test]
+    fn pause_and_resume_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.pause();
+        let reward_at_pause = contract.get_total_pending_reward();
+
+        // Global reward don't accrue when paused
+        context.block_index(200);
+        testing_env!(context.build());
+        assert_eq!(contract.get_total_pending_reward(), reward_at_pause);
+
+        contract.resume();
+        assert!(!contract.is_paused());
+        assert_eq!(contract.total_paused_blocks, 100);
+        assert_eq!(contract.internal_accrual_clock(), 100);
+        assert_eq!(contract.last_block_balance_change, 100);
+        assert_eq!(contract.get_total_pending_reward(), reward_at_pause);
+
+        // Stake after resume accrue from resume point, accrual clock skip paused blocks
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000_000_000_000, None);
+        let account = Account::from(contract.accounts.get(&accounts(2).to_string()).unwrap());
+        assert_eq!(account.last_block_balance_change, 100);
+
+        context.block_index(300);
+        testing_env!(context.build());
+        let config = Config::default();
+        let expected_reward = 20_000_000_000_000 * config.reward_numerator as u128 * 100 / config.reward_denumerator as u128;
+        assert_eq!(contract.get_total_pending_reward().0, reward_at_pause.0 + expected_reward);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CONTRACT_PAUSED")]
+    fn stake_when_paused_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.pause();
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+    }
+
+    #[test]
+    fn migrate_apr_to_basis_points_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        // Legacy config only have total_apr
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            total_apr: 12,
+            apr_basis_points: None,
+            ..Config::default()
+        }, 0, None);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.migrate_apr_to_basis_points();
+        assert_eq!(contract.config.apr_basis_points, Some(1200));
+
+        // Second call is no-op
+        contract.config.total_apr = 20;
+        contract.migrate_apr_to_basis_points();
+        assert_eq!(contract.config.apr_basis_points, Some(1200));
+    }
+
+    #[test]
+    fn harvest_unwrap_near_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        // Reward token is wrap NEAR
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_wrap_near_contract_id(Some(accounts(1).to_string()));
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.set_unwrap_on_harvest(true);
+        assert!(Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()).unwrap_on_harvest);
+        let reward = contract.get_account_reward(accounts(0).to_string()).0;
+        contract.harvest();
+
+        // Unwrap success, reward is paid as native NEAR
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        assert_eq!(contract.near_withdraw_callback(U128(reward), accounts(0).to_string(), accounts(0).to_string(), U128(0)).amount, U128(reward));
+
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.pre_reward, 0);
+        assert_eq!(contract.total_paid_reward_balance, reward);
+    }
+
+    #[test]
+    fn reward_near_u128_max_test() {
+        // Product bigger than u128 but fit U256, result fit u128
+        let reward = calculate_reward(u128::MAX, 1_000, 1_000_000, 1, 1_000_000_000);
+        assert_eq!(u256_to_balance(reward), u128::MAX);
+
+        let reward = calculate_reward(u128::MAX, u32::MAX, 1_000, 1, u32::MAX as u64 * 1_000);
+        assert_eq!(u256_to_balance(reward), u128::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REWARD_OVERFLOW")]
+    fn reward_result_overflow_u128_test() {
+        let reward = calculate_reward(u128::MAX, u32::MAX, 1_000_000, 1, 1);
+        u256_to_balance(reward);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REWARD_OVERFLOW")]
+    fn reward_product_overflow_u256_test() {
+        calculate_reward(u128::MAX, u32::MAX, u64::MAX, u64::MAX, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REWARD_OVERFLOW")]
+    fn global_reward_overflow_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            reward_numerator: u32::MAX,
+            reward_denumerator: 1,
+            ..Config::default()
+        }, 0, None);
+        contract.reward_start_block = Some(0);
+        contract.total_stake_balance = u128::MAX - 1;
+
+        context.block_index(u64::MAX / 2);
+        testing_env!(context.build());
+        contract.internal_calculate_global_reward();
+    }
+
+    #[test]
+    fn dashboard_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 25_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 75_000, None);
+
+        let dashboard = contract.get_dashboard(accounts(0).to_string());
+        assert_eq!(dashboard.account.account_id, accounts(0).to_string());
+        assert_eq!(dashboard.account.stake_balance, U128(25_000));
+        assert_eq!(dashboard.pool.total_stake_balance, U128(100_000));
+        assert_eq!(dashboard.pool.total_stakers, U128(2));
+        assert_eq!(dashboard.pool_share_basis_points, 2_500);
+    }
+
+    #[test]
+    fn harvest_to_allowlist_disabled_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Allowlist disabled, any receiver is accepted
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.harvest_to(accounts(3).to_string());
+    }
+
+    #[test]
+    fn harvest_to_allowlisted_receiver_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.set_harvest_receiver_allowlist_enabled(true);
+        contract.add_harvest_receiver(accounts(2).to_string());
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.harvest_to(accounts(2).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_RECEIVER_NOT_ALLOWED")]
+    fn harvest_to_not_allowlisted_receiver_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.set_harvest_receiver_allowlist_enabled(true);
+        contract.add_harvest_receiver(accounts(2).to_string());
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.harvest_to(accounts(3).to_string());
+    }
+
+    #[test]
+    fn staker_rank_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        for index in [0, 2, 3, 4].iter() {
+            contract.internal_create_account(accounts(*index).to_string());
+        }
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 30_000, None);
+        contract.internal_deposit_and_stake(accounts(3).to_string(), 20_000, None);
+        contract.internal_deposit_and_stake(accounts(4).to_string(), 20_000, None);
+
+        assert_eq!(contract.get_staker_rank(accounts(2).to_string()), 1);
+        assert_eq!(contract.get_staker_rank(accounts(3).to_string()), 2);
+        assert_eq!(contract.get_staker_rank(accounts(4).to_string()), 2);
+        assert_eq!(contract.get_staker_rank(accounts(0).to_string()), 4);
+    }
+
+    #[test]
+    fn referral_bonus_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            referral_bonus_bp: 1_000,
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+        contract.internal_set_referrer(&accounts(0).to_string(), accounts(2).to_string());
+
+        // Set same referrer again is no-op
+        contract.internal_set_referrer(&accounts(0).to_string(), accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        let referee_reward = contract.get_account_reward(accounts(0).to_string()).0;
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Referrer get 10% of referee settled reward
+        let referrer = Account::from(contract.accounts.get(&accounts(2).to_string()).unwrap());
+        assert!(referee_reward > 0);
+        assert_eq!(referrer.pre_reward, referee_reward / 10);
+    }
+
+    #[test]
+    fn referrer_unregistered_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            referral_bonus_bp: 1_000,
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+        contract.internal_set_referrer(&accounts(0).to_string(), accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.internal_remove_account(accounts(2).to_string());
+
+        // Settle skip bonus and drop referrer
+        context.block_index(100);
+        testing_env!(context.build());
+        let referee_reward = contract.get_account_reward(accounts(0).to_string()).0;
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.pre_reward, referee_reward);
+        assert_eq!(account.referrer, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SELF_REFERRAL")]
+    fn self_referral_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_set_referrer(&accounts(0).to_string(), accounts(0).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REFERRER_ALREADY_SET")]
+    fn change_referrer_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+        contract.internal_create_account(accounts(3).to_string());
+        contract.internal_set_referrer(&accounts(0).to_string(), accounts(2).to_string());
+        contract.internal_set_referrer(&accounts(0).to_string(), accounts(3).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REFERRER_AFTER_STAKE")]
+    fn set_referrer_after_stake_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Referrer in later stake msg can't point bonus of existing stake
+        contract.internal_apply_transfer_action(accounts(0).to_string(), 10_000_000_000_000, TransferAction::Stake {
+            lock_tier: None,
+            referrer: Some(accounts(2).to_string())
+        });
+    }
+
+    #[test]
+    fn storage_deposit_refund_one_yocto_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        // Measure storage cost of one account
+        let before_storage_usage = env::storage_usage();
+        contract.internal_create_account(accounts(2).to_string());
+        let storage_used = env::storage_usage() - before_storage_usage;
+        let storage_cost = env::storage_byte_cost() * Balance::from(storage_used);
+
+        // Attach storage cost and 1 yocto, 1 yocto is refunded
+        context.attached_deposit(storage_cost + 1);
+        testing_env!(context.build());
+        assert_eq!(refund_deposit(storage_used), 1);
+
+        // Exact storage cost, nothing refunded
+        context.attached_deposit(storage_cost);
+        testing_env!(context.build());
+        assert_eq!(refund_deposit(storage_used), 0);
+    }
+
+    #[test]
+    fn owner_recalibrate_global_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        let reward_per_100_blocks = contract.get_total_pending_reward().0;
+        assert!(reward_per_100_blocks > 0);
+
+        contract.owner_recalibrate_global(U128(1_000), 100);
+        assert_eq!(contract.pre_reward, 1_000);
+        assert_eq!(contract.last_block_balance_change, 100);
+        assert!(get_logs().iter().any(|log| log.starts_with("EVENT_JSON:") && log.contains("\"event\":\"recalibrate_global\"")));
+
+        // Accrual start from new values
+        assert_eq!(contract.get_total_pending_reward(), U128(1_000));
+        context.block_index(200);
+        testing_env!(context.build());
+        assert_eq!(contract.get_total_pending_reward(), U128(1_000 + reward_per_100_blocks));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner contract can be access")]
+    fn owner_recalibrate_global_not_owner_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.owner_recalibrate_global(U128(0), 0);
+    }
+
+    #[test]
+    fn effective_diff_block_unpaused_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.reward_start_block = Some(10);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        assert_eq!(contract.effective_diff_block(50), 50);
+
+        // Blocks before reward start are not counted
+        assert_eq!(contract.effective_diff_block(0), 90);
+    }
+
+    #[test]
+    fn effective_diff_block_paused_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.reward_start_block = Some(0);
+        contract.paused = true;
+        contract.paused_in_block = 80;
+
+        // Accrual stop at pause block
+        context.block_index(100);
+        testing_env!(context.build());
+        assert_eq!(contract.effective_diff_block(50), 30);
+
+        // Pause block is never later than current block
+        context.block_index(60);
+        testing_env!(context.build());
+        assert_eq!(contract.effective_diff_block(50), 10);
+    }
+
+    #[test]
+    fn effective_diff_block_last_change_after_pause_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.reward_start_block = Some(0);
+        contract.paused = true;
+        contract.paused_in_block = 80;
+
+        // Account settled while paused accrue nothing
+        context.block_index(100);
+        testing_env!(context.build());
+        assert_eq!(contract.effective_diff_block(90), 0);
+    }
+
+    #[test]
+    fn get_rewards_batch_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+
+        let rewards = contract.get_rewards_batch(vec![
+            accounts(0).to_string(),
+            accounts(3).to_string(),
+            accounts(2).to_string()
+        ]);
+        assert_eq!(rewards, vec![
+            (accounts(0).to_string(), contract.get_account_reward(accounts(0).to_string())),
+            (accounts(3).to_string(), U128(0)),
+            (accounts(2).to_string(), U128(0))
+        ]);
+        assert!(rewards[0].1.0 > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BATCH_TOO_LARGE")]
+    fn get_rewards_batch_too_large_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.get_rewards_batch(vec![accounts(0).to_string(); MAX_BATCH + 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOTAL_STAKE_UNDERFLOW")]
+    fn unstake_total_stake_underflow_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Corrupt global total
+        contract.total_stake_balance = 5_000_000_000_000;
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+    }
+
+    #[test]
+    fn get_unstake_status_test() {
+        let mut context = get_context(false);
+        context.epoch_height(10);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            epoch_duration_seconds: Some(3_600),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        let status = contract.get_unstake_status(accounts(0).to_string());
+        assert_eq!(status.epochs_remaining, NUM_EPOCHS_TO_UNLOCK);
+        assert_eq!(status.estimated_seconds_remaining, NUM_EPOCHS_TO_UNLOCK * 3_600);
+        assert!(!status.can_withdraw);
+
+        // Unlocked
+        context.epoch_height(10 + NUM_EPOCHS_TO_UNLOCK);
+        testing_env!(context.build());
+        let status = contract.get_unstake_status(accounts(0).to_string());
+        assert_eq!(status.epochs_remaining, 0);
+        assert_eq!(status.estimated_seconds_remaining, 0);
+        assert!(status.can_withdraw);
+
+        // Nothing to withdraw after withdraw
+        contract.internal_withdraw(accounts(0).to_string());
+        let status = contract.get_unstake_status(accounts(0).to_string());
+        assert_eq!(status.unstake_balance, U128(0));
+        assert!(!status.can_withdraw);
+        assert!(!contract.get_account_info(accounts(0).to_string()).can_withdraw);
+    }
+
+    #[test]
+    fn set_ft_contract_id_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_ft_contract_id(accounts(2).to_string());
+
+        assert_eq!(contract.get_metadata().ft_contract_id, accounts(2).to_string());
+        assert!(get_logs().iter().any(|log| log.starts_with("EVENT_JSON:") && log.contains("\"event\":\"set_ft_contract_id\"")));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_POOL_NOT_EMPTY")]
+    fn set_ft_contract_id_not_empty_pool_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.set_ft_contract_id(accounts(2).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_POOL_NOT_EMPTY")]
+    fn set_ft_contract_id_unpaid_reserve_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        // No stake but reward reserve funded in old token
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.set_ft_contract_id(accounts(2).to_string());
+    }
+
+    #[test]
+    fn accounts_iteration_lookup_parity_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+        contract.internal_create_account(accounts(3).to_string());
+
+        let account_ids = contract.get_accounts(0, 10);
+        assert_eq!(contract.get_number_of_accounts(), 3);
+        assert_eq!(account_ids, vec![accounts(0).to_string(), accounts(2).to_string(), accounts(3).to_string()]);
+        assert!(account_ids.iter().all(|account_id| contract.is_registered(account_id.clone())));
+        assert!(!contract.is_registered(accounts(4).to_string()));
+
+        // Pagination
+        assert_eq!(contract.get_accounts(1, 1), vec![accounts(2).to_string()]);
+        assert_eq!(contract.get_accounts(3, 10).len(), 0);
+    }
+
+    /**
+     * Borsh bytes of baseline account value: UpgradableAccount::Current tag then baseline Account fields
+     */
+    fn baseline_account_fixture(stake_balance: Balance, unstake_balance: Balance, unstake_available_epoch_height: EpochHeight) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![1];
+        bytes.extend_from_slice(&stake_balance.to_le_bytes()); // stake_balance
+        bytes.extend_from_slice(&0u128.to_le_bytes()); // pre_stake_balance
+        bytes.extend_from_slice(&500u128.to_le_bytes()); // pre_reward
+        bytes.extend_from_slice(&10u64.to_le_bytes()); // last_block_balance_change
+        bytes.extend_from_slice(&unstake_balance.to_le_bytes()); // unstake_balance
+        bytes.extend_from_slice(&7u64.to_le_bytes()); // unstake_start_timestamp
+        bytes.extend_from_slice(&unstake_available_epoch_height.to_le_bytes()); // unstake_available_epoch_height
+        bytes
+    }
+
+    fn borsh_string(value: &str) -> Vec<u8> {
+        let mut bytes: Vec<u8> = (value.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn migrate_baseline_state_test() {
+        let mut context = get_context(false);
+        context.block_index(100);
+        testing_env!(context.build());
+
+        // Baseline contract state, written byte by byte so fixture don't depend on current struct definitions
+        let mut state: Vec<u8> = Vec::new();
+        state.extend(borsh_string(&accounts(1).to_string())); // owner_id
+        state.extend(borsh_string("ft_contract")); // ft_contract_id
+        state.extend_from_slice(&715u32.to_le_bytes()); // config.reward_numerator
+        state.extend_from_slice(&100000000000u64.to_le_bytes()); // config.reward_denumerator
+        state.extend_from_slice(&15u32.to_le_bytes()); // config.total_apr
+        state.extend_from_slice(&3_000_000_000_000_000u128.to_le_bytes()); // total_stake_balance
+        state.extend_from_slice(&200u128.to_le_bytes()); // total_paid_reward_balance
+        state.extend_from_slice(&2u128.to_le_bytes()); // total_staker
+        state.extend_from_slice(&1_000u128.to_le_bytes()); // pre_reward
+        state.extend_from_slice(&10u64.to_le_bytes()); // last_block_balance_change
+        state.extend_from_slice(&1u32.to_le_bytes()); // accounts prefix len
+        state.push(0); // accounts prefix StorageKey::AccountKey
+        state.push(0); // paused
+        state.extend_from_slice(&0u64.to_le_bytes()); // paused_in_block
+        env::storage_write(b"STATE", &state);
+
+        for (account_id, stake_balance, unstake_balance) in vec![(accounts(0), 1_000_000_000_000_000u128, 0u128), (accounts(2), 2_000_000_000_000_000, 400)] {
+            let mut key: Vec<u8> = vec![0];
+            key.extend(borsh_string(&account_id.to_string()));
+            env::storage_write(&key, &baseline_account_fixture(stake_balance, unstake_balance, 3));
+        }
+
+        let mut migrated: StakingContract = StakingContract::migrate(vec![accounts(0).to_string(), accounts(2).to_string()], 24);
+        assert_eq!(migrated.owner_id, accounts(1).to_string());
+        assert_eq!(migrated.ft_contract_id, "ft_contract".to_string());
+        assert_eq!(migrated.config.reward_numerator, 715);
+        assert_eq!(migrated.config.reward_denumerator, 100000000000);
+        assert_eq!(migrated.total_stake_balance, 3_000_000_000_000_000);
+        assert_eq!(migrated.total_unstake_balance, 400);
+        assert_eq!(migrated.total_paid_reward_balance, 200);
+        assert_eq!(migrated.pre_reward, 1_000);
+        assert_eq!(migrated.get_number_of_accounts(), 2);
+        assert_eq!(migrated.recount_stakers(), 2);
+
+        // Baseline account kept as Default and converted on read
+        assert!(matches!(migrated.accounts.get(&accounts(2).to_string()).unwrap(), UpgradableAccount::Default(_)));
+        let account_info = migrated.get_account_info(accounts(2).to_string());
+        assert_eq!(account_info.stake_balance, U128(2_000_000_000_000_000));
+        assert_eq!(account_info.unstake_balance, U128(400));
+        assert_eq!(account_info.unstake_available_epoch, 3);
+
+        // Existing pool keep accruing after migrate
+        assert_eq!(migrated.reward_start_block, Some(0));
+        let account = migrated.internal_get_account(&accounts(2).to_string()).unwrap();
+        assert_eq!(account.pre_reward, 500);
+        assert!(migrated.get_account_reward(accounts(2).to_string()).0 > 500);
+        assert!(matches!(migrated.accounts.get(&accounts(2).to_string()).unwrap(), UpgradableAccount::Current(_)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BATCH_TOO_LARGE")]
+    fn storage_deposit_bulk_too_large_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.storage_deposit_bulk(vec![accounts(0).to_string(); MAX_BATCH + 1]);
+    }
+
+    #[test]
+    fn one_year_accrual_matches_apr_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        // 1M token with 18 decimals
+        let principal: Balance = 1_000_000 * 10u128.pow(18);
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), principal);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), principal, None);
+
+        // Settle every month for one year
+        let steps: u64 = 12;
+        for step in 1..=steps {
+            context.block_index(BLOCKS_PER_YEAR * step / steps);
+            testing_env!(context.build());
+
+            let mut account: Account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+            contract.internal_settle_account_reward(&mut account);
+            contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+        }
+
+        let total_reward: Balance = contract.get_account_reward(accounts(0).to_string()).0;
+
+        // Same as one settlement for whole year, only lose rounding of each step
+        let config = Config::default();
+        let expected_reward: Balance = principal * config.reward_numerator as u128 * BLOCKS_PER_YEAR as u128 / config.reward_denumerator as u128;
+        assert!(expected_reward - total_reward <= steps as u128);
+
+        // Within 5 basis points of declared 15% APR
+        let declared_reward: Balance = principal * 15 / 100;
+        let diff: Balance = if total_reward > declared_reward { total_reward - declared_reward } else { declared_reward - total_reward };
+        assert!(diff <= principal * 5 / BASIS_POINTS as u128);
+    }
+
+    #[test]
+    fn stake_event_first_stake_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        let logs = get_logs();
+        assert!(logs.iter().any(|log| log.contains("\"event\":\"stake\"") && log.contains("\"is_first_stake\":true")));
+
+        // Top-up
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        let logs = get_logs();
+        assert!(logs.iter().any(|log| log.contains("\"event\":\"stake\"") && log.contains("\"is_first_stake\":false")));
+    }
+
+    #[test]
+    fn slash_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        let reward_before_slash = contract.get_account_reward(accounts(0).to_string()).0;
+        contract.slash(accounts(0).to_string(), U128(4_000_000_000_000), "double sign".to_string());
+
+        let account_json = contract.get_account_info(accounts(0).to_string());
+        assert_eq!(account_json.stake_balance, U128(6_000_000_000_000));
+        assert_eq!(account_json.reward, U128(reward_before_slash));
+        assert_eq!(contract.total_stake_balance, 6_000_000_000_000);
+        assert_eq!(contract.total_reward_fund, 5_000_000_000_000);
+        assert_eq!(contract.total_staker, 1);
+        assert!(get_logs().iter().any(|log| log.contains("\"event\":\"slash\"") && log.contains("\"reason\":\"double sign\"")));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_MUST_LESS_THAN_BALANCE")]
+    fn slash_more_than_stake_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.slash(accounts(0).to_string(), U128(10_000_000_000_001), "double sign".to_string());
+    }
+
+    fn unstaking_reward(config: Config) -> Balance {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), config, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Unstake all, only unstaking balance is left
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.get_account_reward(accounts(0).to_string()).0
+    }
+
+    #[test]
+    fn accrue_on_unstaking_disabled_test() {
+        assert_eq!(unstaking_reward(Config::default()), 0);
+    }
+
+    #[test]
+    fn accrue_on_unstaking_enabled_test() {
+        let reward = unstaking_reward(Config {
+            accrue_on_unstaking: true,
+            reduced_rate_bp: 5_000,
+            ..Config::default()
+        });
+
+        // Half of stake rate: 10^13 * 715 * 100 / 10^11 / 2
+        assert_eq!(reward, 3_575);
+    }
+
+    #[test]
+    fn unstake_carry_sub_unit_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        // 1000 stake earn 0.4 reward per block
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            reward_numerator: 1,
+            reward_denumerator: 2_500,
+            reward_precision_multiplier: 1_000_000,
+            ..Config::default()
+        }, 2, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 1_000, None);
+
+        // 2 blocks accrue 0.8, below one unit
+        context.block_index(2);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 500);
+
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.pre_reward, 0);
+        assert_eq!(account.reward_remainder, 800_000);
+
+        // Remaining 500 stake accrue 0.2 per block, carried 0.8 + 0.2 pay out one unit
+        context.block_index(3);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 1);
+    }
+
+    #[test]
+    fn ft_registration_callback_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"{\"total\":\"1250000000000000000000\",\"available\":\"0\"}".to_vec())]
+        );
+        assert!(contract.ft_registration_callback());
+
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"null".to_vec())]
+        );
+        assert!(!contract.ft_registration_callback());
+    }
+
+    #[test]
+    fn update_config_split_rate_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Double reward rate at block 100
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.update_config(Config {
+            reward_numerator: 1430,
+            total_apr: 30,
+            ..Config::default()
+        });
+
+        // 100 blocks at old rate and 100 blocks at new rate: 10^13 * (715 + 1430) * 100 / 10^11
+        context.block_index(200);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 21_450);
+
+        // Account settled after update only accrue new rate
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        contract.internal_settle_account_reward(&mut account);
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+        context.block_index(300);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 21_450 + 14_300);
+    }
+
+    #[test]
+    fn update_config_twice_dormant_account_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Double rate at block 100, triple at block 200
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.update_config(Config {
+            reward_numerator: 1430,
+            total_apr: 30,
+            ..Config::default()
+        });
+        context.block_index(200);
+        testing_env!(context.build());
+        contract.update_config(Config {
+            reward_numerator: 2145,
+            total_apr: 45,
+            ..Config::default()
+        });
+
+        // Each 100 blocks at its own rate: 10^13 * (715 + 1430 + 2145) * 100 / 10^11
+        context.block_index(300);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 42_900);
+    }
+
+    #[test]
+    fn owner_withdraw_reserve_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // 7150 reward accrued, reserve over it can be withdrawn
+        context.block_index(100);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.owner_withdraw_reserve(U128(1_000_000 - 7_150));
+        assert_eq!(contract.total_reward_fund, 7_150);
+
+        // Failed transfer restore reserve
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(contract.withdraw_reserve_callback(U128(1_000_000 - 7_150)), U128(0));
+        assert_eq!(contract.total_reward_fund, 1_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSOLVENT")]
+    fn owner_withdraw_reserve_insolvent_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Withdraw whole reserve while 7150 reward is outstanding
+        context.block_index(100);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.owner_withdraw_reserve(U128(1_000_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSOLVENT")]
+    fn owner_withdraw_reserve_boosted_insolvent_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_lock_tiers(vec![LockTier { lock_epochs: 10, boost_basis_points: 20_000 }]);
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, Some(0));
+
+        // 7150 base reward accrued, locked account own 14300
+        context.block_index(100);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 14_300);
+        contract.owner_withdraw_reserve(U128(1_000_000 - 7_150));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSOLVENT")]
+    fn slash_to_treasury_insolvent_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        // Stake is tracked without reward fund, so outstanding reward is not covered
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_treasury_id(Some(accounts(3).to_string()));
+        contract.internal_fund_reward(accounts(1).to_string(), 0);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.slash(accounts(0).to_string(), U128(1_000), "test".to_string());
+    }
+
+    #[test]
+    fn forecast_account_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        assert_eq!(contract.forecast_account_reward(accounts(0).to_string(), 100), U128(7_150));
+        assert_eq!(contract.forecast_account_reward(accounts(0).to_string(), 200), U128(14_300));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FORECAST_IN_PAST")]
+    fn forecast_account_reward_past_block_test() {
+        let mut context = get_context(false);
+        context.block_index(100);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.forecast_account_reward(accounts(0).to_string(), 99);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FORECAST_TOO_FAR")]
+    fn forecast_account_reward_over_cap_test() {
+        let mut context = get_context(false);
+        context.block_index(100);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.forecast_account_reward(accounts(0).to_string(), 100 + MAX_FORECAST_BLOCKS + 1);
+    }
+
+    #[test]
+    fn get_account_info_at_epoch_test() {
+        let mut context = get_context(false);
+        context.epoch_height(10);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 5_000_000_000_000);
+
+        assert!(!contract.get_account_info_at_epoch(accounts(0).to_string(), 10).can_withdraw);
+        assert!(contract.get_account_info_at_epoch(accounts(0).to_string(), 10 + NUM_EPOCHS_TO_UNLOCK).can_withdraw);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FORECAST_IN_PAST")]
+    fn get_account_info_at_past_epoch_test() {
+        let mut context = get_context(false);
+        context.epoch_height(10);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.get_account_info_at_epoch(accounts(0).to_string(), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FORECAST_TOO_FAR")]
+    fn get_account_info_at_epoch_over_cap_test() {
+        let mut context = get_context(false);
+        context.epoch_height(10);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.get_account_info_at_epoch(accounts(0).to_string(), 10 + MAX_FORECAST_EPOCHS + 1);
+    }
+
+    #[test]
+    #[cfg(feature = "camel_case_api")]
+    fn camel_case_api_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        let pool_info_json = near_sdk::serde_json::to_value(contract.get_pool_info()).unwrap();
+        assert!(pool_info_json.get("totalStakeBalance").is_some());
+        assert!(pool_info_json.get("total_stake_balance").is_none());
+
+        let config_json = near_sdk::serde_json::to_value(contract.config).unwrap();
+        assert!(config_json.get("rewardNumerator").is_some());
+    }
+
+    #[test]
+    fn get_withdrawable_accounts_test() {
+        let mut context = get_context(false);
+        context.epoch_height(10);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        for index in vec![0, 2, 3, 4] {
+            contract.internal_create_account(accounts(index).to_string());
+        }
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        for index in vec![0, 2, 3] {
+            contract.internal_deposit_and_stake(accounts(index).to_string(), 10_000_000_000_000, None);
+        }
+
+        // accounts(0) and accounts(2) unstake at epoch 10, accounts(3) unstake at epoch 11
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 5_000_000_000_000);
+        contract.internal_unstake(accounts(2).to_string(), 5_000_000_000_000);
+        context.epoch_height(11);
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(3).to_string(), 5_000_000_000_000);
+
+        assert_eq!(contract.get_withdrawable_accounts(0, 10), vec![accounts(0).to_string(), accounts(2).to_string()]);
+        assert_eq!(contract.get_withdrawable_accounts(1, 1), vec![accounts(2).to_string()]);
+
+        // accounts(4) never staked
+        context.epoch_height(12);
+        testing_env!(context.build());
+        assert_eq!(contract.get_withdrawable_accounts(0, 10), vec![accounts(0).to_string(), accounts(2).to_string(), accounts(3).to_string()]);
+    }
+
+    #[test]
+    fn stake_into_empty_pool_after_idle_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+
+        // Pool is empty for 1M blocks
+        context.block_index(1_000_000);
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        assert_eq!(contract.get_pool_info().total_reward, U128(0));
+
+        // Only accrual after stake is counted
+        context.block_index(1_000_100);
+        testing_env!(context.build());
+        assert_eq!(contract.get_pool_info().total_reward, U128(7_150));
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 7_150);
+    }
+
+    #[test]
+    fn strict_init_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        // Current account is accounts(0)
+        let contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(2).to_string(), Config::default(), 0, Some(true));
+        assert_eq!(contract.ft_contract_id, accounts(2).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_INIT_ACCOUNTS")]
+    fn strict_init_same_owner_and_ft_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config::default(), 0, Some(true));
+    }
+
+    #[test]
+    fn get_reward_breakdown_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        let breakdown = contract.get_reward_breakdown(accounts(0).to_string());
+        assert_eq!(breakdown.settled, U128(0));
+        assert!(breakdown.live.0 > 0);
+        assert_eq!(breakdown.total, breakdown.live);
+
+        // Top-up settle live reward
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        let breakdown = contract.get_reward_breakdown(accounts(0).to_string());
+        assert_eq!(breakdown.settled, U128(7_150));
+        assert_eq!(breakdown.live, U128(0));
+    }
+
+    #[test]
+    fn owner_settle_global_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.owner_settle_global();
+        assert_eq!(contract.pre_reward, 7_150);
+        assert_eq!(contract.last_block_balance_change, 100);
+
+        // Second settlement at same block add zero
+        contract.owner_settle_global();
+        assert_eq!(contract.pre_reward, 7_150);
+    }
+
+    #[test]
+    fn account_pool_share_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        // Empty pool
+        assert_eq!(contract.get_account_info(accounts(0).to_string()).pool_share_basis_points, 0);
+
+        let mut context = get_context(false);
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 20_000_000_000_000, None);
+
+        let share_0 = contract.get_account_info(accounts(0).to_string()).pool_share_basis_points;
+        let share_2 = contract.get_account_info(accounts(2).to_string()).pool_share_basis_points;
+        assert_eq!(share_0, 3_333);
+        assert_eq!(share_2, 6_666);
+        assert!(share_0 + share_2 >= 9_998 && share_0 + share_2 <= BASIS_POINTS);
+    }
+
+    #[test]
+    fn get_time_config_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        let time_config = contract.get_time_config();
+        assert_eq!(time_config.blocks_per_epoch, DEFAULT_BLOCKS_PER_EPOCH);
+        assert_eq!(time_config.epoch_duration_seconds, DEFAULT_EPOCH_DURATION_SECONDS);
+
+        let contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            estimated_blocks_per_epoch: Some(1_000),
+            epoch_duration_seconds: Some(1_500),
+            ..Config::default()
+        }, 0, None);
+        let time_config = contract.get_time_config();
+        assert_eq!(time_config.blocks_per_epoch, 1_000);
+        assert_eq!(time_config.epoch_duration_seconds, 1_500);
+    }
+
+    #[test]
+    fn boost_token_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            max_token_boost_bp: 5_000,
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_boost_ft_contract_id(Some(accounts(3).to_string()));
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Boost equal to stake is capped at 0.5x extra weight
+        context.predecessor_account_id(accounts(3));
+        testing_env!(context.build());
+        contract.internal_deposit_boost(accounts(0).to_string(), 10_000_000_000_000);
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.boost_balance, 10_000_000_000_000);
+        assert_eq!(contract.internal_token_boost(&account), 5_000);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 7_150 * 3 / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_VALID_FT_CONTRACT")]
+    fn boost_from_stake_token_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_boost_ft_contract_id(Some(accounts(3).to_string()));
+        contract.internal_deposit_boost(accounts(0).to_string(), 10_000_000_000_000);
+    }
+
+    #[test]
+    fn ft_on_transfer_zero_amount_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        let account_before = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        match contract.ft_on_transfer(accounts(0).to_string(), U128(0), "".to_string()) {
+            PromiseOrValue::Value(unused_amount) => assert_eq!(unused_amount, U128(0)),
+            PromiseOrValue::Promise(_) => panic!("Expected value")
+        }
+
+        assert_eq!(Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()), account_before);
+        assert_eq!(contract.total_staker, 0);
+        assert_eq!(contract.last_block_balance_change, 0);
+    }
+
+    #[test]
+    fn max_accrued_reward_per_account_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            max_accrued_reward_per_account: Some(U128(10u128.pow(26))),
+            ..Config::default()
+        }, 18, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        // Whale stake 10^9 token with 18 decimals
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10u128.pow(27), None);
+
+        context.block_index(1);
+        testing_env!(context.build());
+        assert!(!contract.get_account_info(accounts(0).to_string()).is_accrual_capped);
+
+        // Dormant for 10 years
+        context.block_index(BLOCKS_PER_YEAR * 10);
+        testing_env!(context.build());
+        let account_json = contract.get_account_info(accounts(0).to_string());
+        assert_eq!(account_json.reward, U128(10u128.pow(26)));
+        assert!(account_json.is_accrual_capped);
+
+        // Settlement keep reward at cap
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10u128.pow(18), None);
+        context.block_index(BLOCKS_PER_YEAR * 11);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 10u128.pow(26));
+    }
+
+    #[test]
+    fn debug_get_account_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.epoch_height(10);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 4_000_000_000_000);
+
+        let account = contract.debug_get_account(accounts(0).to_string());
+        assert_eq!(account.stake_balance, U128(6_000_000_000_000));
+        assert_eq!(account.unstake_balance, U128(4_000_000_000_000));
+        assert_eq!(account.unstake_available_epoch_height, 10 + NUM_EPOCHS_TO_UNLOCK);
+        assert_eq!(account.last_block_balance_change, 100);
+        assert_eq!(account.pre_reward, U128(7_150));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner contract can be access")]
+    fn debug_get_account_not_owner_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.debug_get_account(accounts(0).to_string());
+    }
+
+    #[test]
+    fn set_transfer_memos_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        assert_eq!(contract.harvest_memo, DEFAULT_HARVEST_MEMO);
+        assert_eq!(contract.withdraw_memo, DEFAULT_WITHDRAW_MEMO);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_transfer_memos("pool-harvest".to_string(), "pool-withdraw".to_string());
+        assert_eq!(contract.harvest_memo, "pool-harvest");
+        assert_eq!(contract.withdraw_memo, "pool-withdraw");
+    }
+
+    #[test]
+    fn sync_reward_reserve_callback_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000, None);
+        contract.total_paid_reward_balance = 100;
+
+        // 500 token transferred directly to contract
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"11400\"".to_vec())]
+        );
+        assert_eq!(contract.sync_reward_reserve_callback(), U128(1_400));
+        assert_eq!(contract.total_reward_fund, 1_500);
+        assert!(get_logs().iter().any(|log| log.contains("\"event\":\"sync_reward_reserve\"")));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OPERATION_IN_FLIGHT")]
+    fn harvest_during_withdraw_in_flight_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        context.epoch_height(5);
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.internal_withdraw(accounts(0).to_string());
+        assert!(Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()).operation_in_flight);
+
+        // Withdraw callback is not resolved yet
+        contract.harvest();
+    }
+
+    #[test]
+    fn operation_in_flight_cleared_in_callback_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.harvest();
+        assert!(Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()).operation_in_flight);
+
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.ft_transfer_callback(U128(7_150), accounts(0).to_string(), U128(0));
+        assert!(!Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()).operation_in_flight);
+
+        testing_env!(context.build());
+        contract.harvest();
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.ft_transfer_callback(U128(7_150), accounts(0).to_string(), U128(0));
+        assert!(!Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()).operation_in_flight);
+    }
+
+    #[test]
+    fn get_unstake_tranches_test() {
+        let mut context = get_context(false);
+        context.epoch_height(10);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        assert!(contract.get_unstake_tranches(accounts(0).to_string()).is_empty());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 20_000_000_000_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        let tranches = contract.get_unstake_tranches(accounts(0).to_string());
+        assert_eq!(tranches.len(), 1);
+        assert_eq!(tranches[0].amount, U128(10_000_000_000_000));
+        assert_eq!(tranches[0].available_epoch, 10 + NUM_EPOCHS_TO_UNLOCK);
+        assert!(!tranches[0].can_withdraw);
+
+        // Second unstake at later epoch merge into single tranche with later unlock epoch
+        context.epoch_height(11);
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        let tranches = contract.get_unstake_tranches(accounts(0).to_string());
+        assert_eq!(tranches.len(), 1);
+        assert_eq!(tranches[0].amount, U128(20_000_000_000_000));
+        assert_eq!(tranches[0].available_epoch, 11 + NUM_EPOCHS_TO_UNLOCK);
+
+        context.epoch_height(11 + NUM_EPOCHS_TO_UNLOCK);
+        testing_env!(context.build());
+        assert!(contract.get_unstake_tranches(accounts(0).to_string())[0].can_withdraw);
+    }
+
+    #[test]
+    fn set_apr_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 10_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.set_apr(2000);
+        assert_eq!(contract.config.effective_apr_basis_points(), 2000);
+        assert_eq!(contract.config.apr_basis_points, Some(2000));
+        assert_eq!(contract.config.total_apr, 20);
+
+        // Reward before config change accrue at old rate, one year after accrue ~20%
+        context.block_index(100 + BLOCKS_PER_YEAR);
+        testing_env!(context.build());
+        let reward = contract.get_account_reward(accounts(0).to_string()).0 - 7_150;
+        assert!(reward >= 1_999_000_000_000 && reward <= 2_000_000_000_000);
+    }
+
+    #[test]
+    fn storage_unregister_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        match contract.storage_unregister(None) {
+            PromiseOrValue::Value(removed) => assert!(removed),
+            PromiseOrValue::Promise(_) => panic!("Expected value")
+        }
+        assert!(!contract.is_registered(accounts(0).to_string()));
+
+        // Unregistered account
+        match contract.storage_unregister(None) {
+            PromiseOrValue::Value(removed) => assert!(!removed),
+            PromiseOrValue::Promise(_) => panic!("Expected value")
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REWARD_NOT_HARVESTED")]
+    fn storage_unregister_residual_reward_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        account.pre_reward = 1_000;
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn storage_unregister_force_callback_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        account.pre_reward = 1_000;
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.storage_unregister(Some(true));
+        assert!(Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap()).operation_in_flight);
+
+        // Transfer failed, account and reward are kept
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert!(!contract.storage_unregister_callback(accounts(0).to_string(), U128(1_000), U128(0)));
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.pre_reward, 1_000);
+        assert!(!account.operation_in_flight);
+
+        // Transfer success, account is removed
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        assert!(contract.storage_unregister_callback(accounts(0).to_string(), U128(1_000), U128(0)));
+        assert!(!contract.is_registered(accounts(0).to_string()));
+        assert_eq!(contract.total_paid_reward_balance, 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VESTING_ENABLED")]
+    fn storage_unregister_force_vesting_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            vesting_blocks: Some(100),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        account.pre_reward = 1_000;
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_HARVEST_COOLDOWN")]
+    fn storage_unregister_force_cooldown_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1);
+        context.block_index(50);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            harvest_cooldown_blocks: Some(100),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        account.pre_reward = 1_000;
+        account.last_harvest_block = 10;
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    fn checkpoint_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.checkpoint_reward();
+
+        let log = get_logs().into_iter().find(|log| log.contains("\"event\":\"checkpoint_reward\"")).unwrap();
+        let event: near_sdk::serde_json::Value = near_sdk::serde_json::from_str(log.trim_start_matches("EVENT_JSON:")).unwrap();
+        let data = &event["data"][0];
+        assert_eq!(data["block"], 100);
+        assert_eq!(data["total_stake_balance"], "10000000000000");
+        assert_eq!(data["pre_reward"], "7150");
+        assert_eq!(data["config"], near_sdk::serde_json::to_value(contract.config).unwrap());
+
+        // Checkpoint don't settle global reward
+        assert_eq!(contract.pre_reward, 0);
+        assert_eq!(contract.last_block_balance_change, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_APR_MISMATCH")]
+    fn strict_init_apr_mismatch_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        // Default reward rate is ~15% APR
+        StakingContract::new(accounts(1).to_string(), accounts(2).to_string(), Config {
+            total_apr: 30,
+            ..Config::default()
+        }, 0, Some(true));
+    }
+
+    #[test]
+    fn update_config_apr_mismatch_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        let mismatched_config = Config {
+            total_apr: 30,
+            ..Config::default()
+        };
+
+        // Not strict, mismatch is only logged
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.update_config(mismatched_config);
+        assert!(get_logs().iter().any(|log| log.contains("\"event\":\"apr_mismatch\"")));
+        assert_eq!(contract.config.total_apr, 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_APR_MISMATCH")]
+    fn update_config_strict_apr_mismatch_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_strict_config(true);
+        contract.update_config(Config {
+            total_apr: 30,
+            ..Config::default()
+        });
+    }
+
+    #[test]
+    fn transfer_stake_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
+        assert_eq!(contract.total_staker, 1);
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.transfer_stake(accounts(2).to_string(), U128(10_000_000_000_000));
+
+        let sender = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        let receiver = Account::from(contract.accounts.get(&accounts(2).to_string()).unwrap());
+        assert_eq!(sender.stake_balance, 20_000_000_000_000);
+        assert_eq!(receiver.stake_balance, 10_000_000_000_000);
+        assert_eq!(sender.last_block_balance_change, 100);
+        assert_eq!(receiver.last_block_balance_change, 100);
+        assert_eq!(contract.total_stake_balance, 30_000_000_000_000);
+        assert_eq!(contract.total_staker, 2);
+        assert!(get_logs().iter().any(|log| log.contains("\"event\":\"stake_transfer\"")));
+
+        // Transfer all remaining stake
+        contract.transfer_stake(accounts(2).to_string(), U128(20_000_000_000_000));
+        assert_eq!(Account::from(contract.accounts.get(&accounts(2).to_string()).unwrap()).stake_balance, 30_000_000_000_000);
+        assert_eq!(contract.total_stake_balance, 30_000_000_000_000);
+        assert_eq!(contract.total_staker, 1);
+    }
+
+    #[test]
+    fn transfer_stake_mutual_referral_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            referral_bonus_bp: 1_000,
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+        contract.internal_set_referrer(&accounts(0).to_string(), accounts(2).to_string());
+        contract.internal_set_referrer(&accounts(2).to_string(), accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let reward = contract.get_account_reward(accounts(0).to_string()).0;
+        assert!(reward > 0);
+        contract.transfer_stake(accounts(2).to_string(), U128(5_000_000_000_000));
+
+        // Bonus credited to each other during settle is kept
+        let sender = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        let receiver = Account::from(contract.accounts.get(&accounts(2).to_string()).unwrap());
+        assert_eq!(sender.pre_reward, reward + reward / 10);
+        assert_eq!(receiver.pre_reward, reward + reward / 10);
+        assert_eq!(sender.stake_balance, 5_000_000_000_000);
+        assert_eq!(receiver.stake_balance, 15_000_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_FOUND_ACCOUNT")]
+    fn transfer_stake_unregistered_receiver_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.transfer_stake(accounts(2).to_string(), U128(10_000_000_000_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OPERATION_IN_FLIGHT")]
+    fn transfer_stake_withdraw_in_flight_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 20_000_000_000_000, None);
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        // Withdraw transfer pending, its failure would restore unstake balance
+        context.epoch_height(10);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.withdraw();
+        contract.transfer_stake(accounts(2).to_string(), U128(10_000_000_000_000));
+    }
+
+    #[test]
+    fn owner_credit_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(1));
+        context.attached_deposit(10u128.pow(24));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.owner_credit_reward(accounts(0).to_string(), U128(1_000));
+        assert!(contract.is_registered(accounts(0).to_string()));
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 1_000);
+        assert_eq!(contract.pre_reward, 1_000);
+        assert_eq!(contract.total_staker, 0);
+
+        // Non-staker harvest credited reward
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.harvest();
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        assert_eq!(contract.ft_transfer_callback(U128(1_000), accounts(0).to_string(), U128(0)).amount, U128(1_000));
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 0);
+        assert_eq!(contract.total_paid_reward_balance, 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSOLVENT")]
+    fn owner_credit_reward_insolvent_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(10u128.pow(24));
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000);
+        contract.owner_credit_reward(accounts(0).to_string(), U128(1_001));
+    }
+
+    #[test]
+    #[cfg(not(feature = "camel_case_api"))]
+    fn token_amount_json_string_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        // Amount bigger than 2^53 lose precision in JSON number
+        let large_amount: Balance = 10u128.pow(30);
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), large_amount, None);
+        contract.internal_unstake(accounts(0).to_string(), large_amount / 2);
+
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        account.pre_reward = large_amount;
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+
+        let reward = near_sdk::serde_json::to_value(contract.get_account_reward(accounts(0).to_string())).unwrap();
+        assert_eq!(reward, near_sdk::serde_json::json!(large_amount.to_string()));
+
+        let breakdown = near_sdk::serde_json::to_value(contract.get_reward_breakdown(accounts(0).to_string())).unwrap();
+        assert!(breakdown["settled"].is_string() && breakdown["live"].is_string() && breakdown["total"].is_string());
+
+        let pool_info = near_sdk::serde_json::to_value(contract.get_pool_info()).unwrap();
+        assert!(pool_info["total_stake_balance"].is_string() && pool_info["total_unstake_balance"].is_string());
+
+        let status = near_sdk::serde_json::to_value(contract.get_unstake_status(accounts(0).to_string())).unwrap();
+        assert!(status["unstake_balance"].is_string());
+
+        let tranches = near_sdk::serde_json::to_value(contract.get_unstake_tranches(accounts(0).to_string())).unwrap();
+        assert!(tranches[0]["amount"].is_string());
+
+        let account_json = near_sdk::serde_json::to_value(contract.get_account_info(accounts(0).to_string())).unwrap();
+        assert!(account_json["stake_balance"].is_string() && account_json["reward"].is_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        let debug_account = near_sdk::serde_json::to_value(contract.debug_get_account(accounts(0).to_string())).unwrap();
+        assert!(debug_account["stake_balance"].is_string() && debug_account["pre_reward"].is_string() && debug_account["reward_remainder"].is_string());
+    }
+
+    #[test]
+    fn update_config_denumerator_split_rate_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Only lower denumerator at block 100, reward rate is doubled
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.update_config(Config {
+            reward_denumerator: 50_000_000_000,
+            total_apr: 30,
+            ..Config::default()
+        });
+
+        // Dormant account: 100 blocks at old denumerator and 100 blocks at new one: 7_150 + 10^13 * 715 * 100 / (5 * 10^10)
+        context.block_index(200);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 7_150 + 14_300);
+        assert_eq!(contract.get_total_pending_reward().0, 7_150 + 14_300);
+    }
+
+    #[test]
+    fn ft_on_transfer_invalid_msg_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        match contract.ft_on_transfer(accounts(0).to_string(), U128(10_000_000_000_000), "{\"action\":".to_string()) {
+            PromiseOrValue::Value(unused_amount) => assert_eq!(unused_amount, U128(10_000_000_000_000)),
+            PromiseOrValue::Promise(_) => panic!("Expected value")
+        }
+        assert!(get_logs().iter().any(|log| log.contains("\"event\":\"invalid_msg\"") && log.contains("ERR_INVALID_MSG")));
+        assert_eq!(contract.total_stake_balance, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_POOL_HARVEST_THROTTLED")]
+    fn pool_harvest_throttled_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.set_harvest_window_blocks(100);
+        contract.set_max_harvest_per_window(Some(U128(10_000)));
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.harvest();
+        assert_eq!(contract.harvested_in_window, 7_150);
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.ft_transfer_callback(U128(7_150), accounts(0).to_string(), U128(0));
+
+        // Another 3_575 in same window exceed limit
+        context.block_index(150);
+        testing_env!(context.build());
+        contract.harvest();
+    }
+
+    #[test]
+    fn pool_harvest_window_reset_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.set_harvest_window_blocks(100);
+        contract.set_max_harvest_per_window(Some(U128(10_000)));
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.harvest();
+        assert_eq!(contract.harvested_in_window, 7_150);
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.ft_transfer_callback(U128(7_150), accounts(0).to_string(), U128(0));
+
+        // New window start after harvest_window_blocks
+        context.block_index(200);
+        testing_env!(context.build());
+        contract.harvest();
+        assert_eq!(contract.harvest_window_start, 200);
+        assert_eq!(contract.harvested_in_window, 7_150);
+    }
+
+    #[test]
+    fn default_account_upgraded_on_mutation_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        let account_v1 = AccountV1 {
+            stake_balance: 0,
+            pre_stake_balance: 0,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            unstake_balance: 0,
+            unstake_start_timestamp: 0,
+            unstake_available_epoch_height: 0
+        };
+        let account = Account::from(account_v1.clone());
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::Default(account_v1));
+
+        // View don't write account back
+        contract.get_account_info(accounts(0).to_string());
+        assert!(matches!(contract.accounts.get(&accounts(0).to_string()).unwrap(), UpgradableAccount::Default(_)));
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.set_unwrap_on_harvest(false);
+        match contract.accounts.get(&accounts(0).to_string()).unwrap() {
+            UpgradableAccount::Current(current_account) => assert_eq!(current_account, account),
+            UpgradableAccount::Default(_) => panic!("Expected current account")
+        }
+    }
+
+    #[test]
+    fn estimate_reward_for_amount_test() {
+        let context = get_context(true);
+        testing_env!(context.build());
+
+        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        let config = Config::default();
+
+        // 10^13 * 715 * 57_600 / 10^11, one day of blocks
+        let expected_reward = 10_000_000_000_000 * config.reward_numerator as u128 * 57_600 / config.reward_denumerator as u128;
+        assert_eq!(contract.estimate_reward_for_amount(U128(10_000_000_000_000), 57_600), U128(expected_reward));
+        assert_eq!(contract.estimate_reward_for_amount(U128(10_000_000_000_000), 0), U128(0));
+
+        // Product bigger than u128 is computed in U256
+        assert_eq!(contract.estimate_reward_for_amount(U128(u128::MAX), 100), U128(243_301_892_348_471_001_376_312_844_313_714));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_CONTRACT_BALANCE")]
+    fn withdraw_insufficient_contract_balance_test() {
+        let mut context = get_context(false);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000, None);
+
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000);
+
+        // Contract only hold 5_000 of 20_000 principal
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"5000\"".to_vec())]
+        );
+        contract.sync_reward_reserve_callback();
+        assert_eq!(contract.ft_balance_shortfall, 15_000);
+
+        // Withdraw rejected before transfer
+        context.epoch_height(5);
+        testing_env!(context.build());
+        contract.internal_withdraw(accounts(0).to_string());
+    }
+
+    #[test]
+    fn two_stakers_reward_proportional_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000_000_000_000, None);
+
+        // Reward follow individual stake, not total pool stake
+        context.block_index(100);
+        testing_env!(context.build());
+        let reward_a = contract.get_account_reward(accounts(0).to_string()).0;
+        let reward_b = contract.get_account_reward(accounts(2).to_string()).0;
+        assert_eq!(reward_b, 7_150);
+        assert_eq!(reward_a, 3 * reward_b);
+        assert_eq!(reward_a + reward_b, contract.get_total_pending_reward().0);
+    }
+
+    #[test]
+    fn two_stakers_reward_proportional_partial_interval_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
+
+        // B stake later, A reward before B join is A only
+        context.block_index(50);
+        testing_env!(context.build());
+        let reward_a_before_join = contract.get_account_reward(accounts(0).to_string()).0;
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        let reward_a = contract.get_account_reward(accounts(0).to_string()).0;
+        let reward_b = contract.get_account_reward(accounts(2).to_string()).0;
+        assert_eq!(reward_b, 3_575);
+        assert_eq!(reward_a - reward_a_before_join, 3 * reward_b);
+        assert_eq!(reward_a + reward_b, contract.get_total_pending_reward().0);
+    }
+
+    #[test]
+    fn harvest_fee_to_treasury_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            harvest_fee_basis_points: 1_000,
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.set_treasury_id(Some(accounts(3).to_string()));
+
+        // Fee leave reward reserve when moved to treasury
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.internal_harvest_success(accounts(0).to_string(), 6_435, 715);
+        assert_eq!(contract.total_fee_collected, 715);
+        assert_eq!(contract.total_reward_fund, 1_000_000 - 715);
+
+        // Treasury transfer failed, fee is restored to reserve
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.withdraw_reserve_callback(U128(715));
+        assert_eq!(contract.total_reward_fund, 1_000_000);
+    }
+
+    #[test]
+    fn get_accrual_status_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        // Reward never funded
+        assert_eq!(contract.get_accrual_status(), AccrualStatus::BudgetExhausted);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 10_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        assert_eq!(contract.get_accrual_status(), AccrualStatus::Active);
+
+        // 14_300 accrued reward exceed 10_000 reserve
+        context.block_index(200);
+        testing_env!(context.build());
+        assert_eq!(contract.get_accrual_status(), AccrualStatus::BudgetExhausted);
+
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        assert_eq!(contract.get_accrual_status(), AccrualStatus::Active);
+
+        contract.ft_balance_shortfall = 1;
+        assert_eq!(contract.get_accrual_status(), AccrualStatus::ReserveBreaker);
+
+        contract.pause();
+        assert_eq!(contract.get_accrual_status(), AccrualStatus::Paused);
+    }
+
+    #[test]
+    fn harvest_callback_keep_interim_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        contract.harvest();
+
+        // Callback executed 2 blocks after harvest
+        context.block_index(102);
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.ft_transfer_callback(U128(7_150), accounts(0).to_string(), U128(0));
+
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.pre_reward, 143);
+        assert_eq!(account.last_block_balance_change, 102);
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 143);
+    }
+
+    #[test]
+    fn storage_deposit_registration_fee_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let registration_fee: Balance = 10u128.pow(22);
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            registration_fee: U128(registration_fee),
+            ..Config::default()
+        }, 0, None);
+
+        // Deposit cover storage and fee, excess is refunded
+        context.attached_deposit(10u128.pow(24));
+        testing_env!(context.build());
+        contract.storage_deposit(None);
+        assert!(contract.is_registered(accounts(0).to_string()));
+        assert_eq!(contract.total_registration_fee, registration_fee);
+
+        // Registered account don't pay fee again
+        contract.storage_deposit(None);
+        assert_eq!(contract.total_registration_fee, registration_fee);
+
+        context.predecessor_account_id(accounts(1));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        assert_eq!(contract.owner_withdraw_registration_fee(), U128(registration_fee));
+        assert_eq!(contract.total_registration_fee, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover storage and registration fee")]
+    fn storage_deposit_without_registration_fee_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            registration_fee: U128(10u128.pow(24)),
+            ..Config::default()
+        }, 0, None);
+
+        // Enough for storage only
+        context.attached_deposit(10u128.pow(22));
+        testing_env!(context.build());
+        contract.storage_deposit(None);
+    }
+
+    #[test]
+    fn preview_unlock_epoch_test() {
+        let mut context = get_context(false);
+        context.epoch_height(10);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        let preview_epoch = contract.preview_unlock_epoch();
+        assert_eq!(preview_epoch, 10 + NUM_EPOCHS_TO_UNLOCK);
+
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.unstake_available_epoch_height, preview_epoch);
+    }
+
+    #[test]
+    fn per_second_time_basis_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.block_timestamp(0);
+        testing_env!(context.build());
+
+        let config = Config {
+            time_basis: TimeBasis::PerSecond,
+            ..Config::default()
+        };
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), config, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Many blocks in few seconds accrue only for elapsed seconds
+        context.block_index(1_000);
+        context.block_timestamp(10 * NANOSECONDS_PER_SECOND);
+        testing_env!(context.build());
+        let expected_reward = 10_000_000_000_000 * config.reward_numerator as u128 * 10 / config.reward_denumerator as u128;
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, expected_reward);
+
+        // No time elapsed, no reward whatever blocks passed
+        context.block_index(2_000);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, expected_reward);
+
+        context.block_timestamp(20 * NANOSECONDS_PER_SECOND);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, expected_reward * 2);
+    }
+
+    #[test]
+    fn per_second_forecast_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.block_timestamp(0);
+        testing_env!(context.build());
+
+        let config = Config {
+            time_basis: TimeBasis::PerSecond,
+            ..Config::default()
+        };
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), config, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 1_000, None);
+
+        // 57_600 blocks ~ 38_400 seconds by BLOCKS_PER_YEAR and SECONDS_PER_YEAR
+        let expected_reward: u128 = 10_000_000_000_000 * config.reward_numerator as u128 * 38_400 / config.reward_denumerator as u128;
+        assert_eq!(contract.estimate_reward_for_amount(U128(10_000_000_000_000), 57_600).0, expected_reward);
+        assert_eq!(contract.forecast_account_reward(accounts(0).to_string(), 57_600).0, expected_reward);
+
+        // ceil(10^11 / (1_000 * 715)) = 139_861 seconds, covered by ceil(139_861 * 2 / 3) blocks
+        assert_eq!(contract.blocks_until_first_reward(accounts(2).to_string()), 93_241);
+    }
+
+    #[test]
+    fn blocks_until_first_reward_remainder_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        // 100 stake earn 0.25 reward per block
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            reward_numerator: 1,
+            reward_denumerator: 400,
+            reward_precision_multiplier: 1_000_000,
+            ..Config::default()
+        }, 2, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 100, None);
+        assert_eq!(contract.blocks_until_first_reward(accounts(0).to_string()), 4);
+
+        // Carried remainder of one block count toward first unit
+        let mut account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        context.block_index(1);
+        testing_env!(context.build());
+        contract.internal_settle_account_reward(&mut account);
+        assert_eq!(account.reward_remainder, 250_000);
+        contract.accounts.insert(&accounts(0).to_string(), &UpgradableAccount::from(account));
+        assert_eq!(contract.blocks_until_first_reward(accounts(0).to_string()), 3);
+
+        context.block_index(3);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 0);
+
+        context.block_index(4);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_INITIALIZED")]
+    fn init_twice_test() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        env::state_write(&contract);
+
+        StakingContract::new_default_config(accounts(2).to_string(), accounts(2).to_string(), 0);
+    }
+
+    #[test]
+    fn compound_effective_apy_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        let nominal_apr: u32 = contract.config.effective_apr_basis_points();
+        assert_eq!(contract.get_effective_apy(accounts(0).to_string()), nominal_apr);
+
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        for round in 1..=3 {
+            context.block_index(round * 100);
+            testing_env!(context.build());
+            let reward: Balance = contract.get_account_reward(accounts(0).to_string()).0;
+            let stake_before: Balance = contract.total_stake_balance;
+
+            assert_eq!(contract.compound(), U128(reward));
+            assert_eq!(contract.total_stake_balance, stake_before + reward);
+            assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 0);
+
+            let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+            assert_eq!(account.compound_count, round);
+            assert_eq!(account.last_compound_block, round * 100);
+        }
+
+        assert!(contract.get_effective_apy(accounts(0).to_string()) > nominal_apr);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VESTING_ENABLED")]
+    fn compound_vesting_enabled_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            vesting_blocks: Some(100),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.compound();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_HARVEST_COOLDOWN")]
+    fn compound_harvest_cooldown_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            harvest_cooldown_blocks: Some(1_000),
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // First compound start cooldown, second one in cooldown is rejected
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.compound();
+
+        context.block_index(200);
+        testing_env!(context.build());
+        contract.compound();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_POOL_HARVEST_THROTTLED")]
+    fn compound_pool_harvest_window_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.set_harvest_window_blocks(1_000);
+        contract.set_max_harvest_per_window(Some(U128(5_000)));
+
+        // 7_150 reward exceed window limit
+        context.block_index(100);
+        context.predecessor_account_id(accounts(0));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.compound();
+    }
+
+    #[test]
+    fn owner_settle_all_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+        contract.internal_create_account(accounts(2).to_string());
+        contract.internal_create_account(accounts(3).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        contract.internal_deposit_and_stake(accounts(2).to_string(), 20_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        let reward_0: Balance = contract.get_account_reward(accounts(0).to_string()).0;
+        let reward_2: Balance = contract.get_account_reward(accounts(2).to_string()).0;
+        assert!(reward_0 > 0 && reward_2 > 0);
+
+        assert_eq!(contract.owner_settle_all(0, 2), 2);
+        assert_eq!(contract.owner_settle_all(2, 10), 1);
+        assert_eq!(contract.owner_settle_all(3, 10), 0);
+
+        for (account_id, reward) in vec![(accounts(0), reward_0), (accounts(2), reward_2)] {
+            let breakdown: RewardBreakdown = contract.get_reward_breakdown(account_id.to_string());
+            assert_eq!(breakdown.live, U128(0));
+            assert_eq!(breakdown.settled, U128(reward));
+            let account = Account::from(contract.accounts.get(&account_id.to_string()).unwrap());
+            assert_eq!(account.last_block_balance_change, 100);
+        }
+    }
+
+    #[test]
+    fn pause_freeze_account_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        contract.pause();
+        let reward_at_pause: Balance = contract.get_account_reward(accounts(0).to_string()).0;
+        assert!(reward_at_pause > 0);
+
+        context.block_index(500);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, reward_at_pause);
+
+        // Account not settled during pause accrue again from resume block, paused blocks are skipped
+        contract.resume();
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, reward_at_pause);
+
+        context.block_index(600);
+        testing_env!(context.build());
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, reward_at_pause * 2);
+    }
+
+    #[test]
+    fn pause_many_times_dormant_account_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // 3 rounds of 100 active blocks then 50 paused blocks
+        for round in 0..3 {
+            context.block_index(round * 150 + 100);
+            testing_env!(context.build());
+            contract.pause();
+            context.block_index(round * 150 + 150);
+            testing_env!(context.build());
+            contract.resume();
+        }
+        assert_eq!(contract.total_paused_blocks, 150);
+
+        // Only 300 active blocks accrue: 10^13 * 715 * 300 / 10^11
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 21_450);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MAX_ACCOUNTS")]
+    fn max_accounts_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.set_max_accounts(Some(2));
+        assert_eq!(contract.config.max_accounts, Some(2));
+
+        context.attached_deposit(10u128.pow(24));
+        testing_env!(context.build());
+        contract.storage_deposit_bulk(vec![accounts(2).to_string(), accounts(3).to_string()]);
+        assert_eq!(contract.get_number_of_accounts(), 2);
+
+        // Registered account is still accepted at cap
+        contract.storage_deposit(Some(accounts(2).to_string()));
+
+        contract.storage_deposit(Some(accounts(4).to_string()));
+    }
+
+    #[test]
+    fn get_reward_params_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(50);
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 5_000_000_000_000, None);
+
+        context.block_index(170);
+        testing_env!(context.build());
+        contract.pause();
+
+        context.block_index(200);
+        testing_env!(context.build());
+
+        // Client side reconstruction of global reward
+        let params: RewardParams = contract.get_reward_params();
+        let clock = if params.is_paused { std::cmp::min(params.paused_in_block, params.current_block) } else { params.current_block };
+        let lasted_block = clock - params.total_paused_blocks;
+        let diff = match params.reward_start_block {
+            Some(reward_start_block) => lasted_block.saturating_sub(std::cmp::max(params.last_block_balance_change, reward_start_block)),
+            None => 0
+        };
+        let total_reward = params.pre_reward.0
+            + params.total_stake_balance.0 * params.reward_numerator as u128 * diff as u128 / params.reward_denumerator as u128;
+
+        assert_eq!(params.current_block, 200);
+        assert!(total_reward > 0);
+        assert_eq!(total_reward, contract.get_pool_info().total_reward.0);
+    }
+
+    #[test]
+    fn get_reward_params_unstaking_accrual_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.block_timestamp(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            accrue_on_unstaking: true,
+            reduced_rate_bp: 5_000,
+            time_basis: TimeBasis::PerSecond,
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 30_000_000_000_000, None);
+
+        context.block_index(100);
+        context.block_timestamp(40 * NANOSECONDS_PER_SECOND);
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 10_000_000_000_000);
+
+        context.block_index(1_000);
+        context.block_timestamp(130 * NANOSECONDS_PER_SECOND);
+        testing_env!(context.build());
+
+        // Client side reconstruction of global reward with unstaking accrual, clock is unix seconds
+        let params: RewardParams = contract.get_reward_params();
+        assert_eq!(params.time_basis, TimeBasis::PerSecond);
+        assert_eq!(params.current_block, 130);
+        let clock = if params.is_paused { std::cmp::min(params.paused_in_block, params.current_block) } else { params.current_block };
+        let lasted_block = clock - params.total_paused_blocks;
+        let diff = match params.reward_start_block {
+            Some(reward_start_block) => lasted_block.saturating_sub(std::cmp::max(params.last_block_balance_change, reward_start_block)),
+            None => 0
+        };
+        let mut total_reward = params.pre_reward.0
+            + params.total_stake_balance.0 * params.reward_numerator as u128 * diff as u128 / params.reward_denumerator as u128;
+        if params.accrue_on_unstaking {
+            let unstake_reward = params.total_unstake_balance.0 * params.reward_numerator as u128 * diff as u128 / params.reward_denumerator as u128;
+            total_reward += unstake_reward * params.reduced_rate_bp as u128 / BASIS_POINTS as u128;
+        }
+
+        assert!(params.total_unstake_balance.0 > 0);
+        assert!(total_reward > params.pre_reward.0);
+        assert_eq!(total_reward, contract.get_pool_info().total_reward.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_REWARD_BELOW_ONE_UNIT")]
+    fn harvest_sub_unit_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        // 1000 stake earn 0.25 reward per block
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            reward_numerator: 1,
+            reward_denumerator: 4_000,
+            reward_precision_multiplier: 1_000_000,
+            ..Config::default()
+        }, 2, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 1_000, None);
+
+        context.block_index(2);
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        assert_eq!(contract.get_harvestable_reward(accounts(0).to_string()), U128(0));
+
+        contract.harvest();
+    }
+
+    #[test]
+    fn permissioned_registration_owner_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            permissioned_registration: true,
+            ..Config::default()
+        }, 0, None);
+
+        // Owner pay and register third party
+        context.predecessor_account_id(accounts(1));
+        context.attached_deposit(10u128.pow(24));
+        testing_env!(context.build());
+        contract.storage_deposit(Some(accounts(2).to_string()));
+        assert!(contract.is_registered(accounts(2).to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ONLY_OWNER_CONTRACT")]
+    fn permissioned_registration_self_register_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            permissioned_registration: true,
+            ..Config::default()
+        }, 0, None);
+
+        context.predecessor_account_id(accounts(2));
+        context.attached_deposit(10u128.pow(24));
+        testing_env!(context.build());
+        contract.storage_deposit(None);
+    }
+
+    #[test]
+    fn stake_increase_mid_interval_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Stake increase settle old balance up to change block, new balance accrue after
+        context.block_index(10);
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 20_000_000_000_000, None);
+
+        context.block_index(25);
+        testing_env!(context.build());
+        let config = Config::default();
+        let old_balance_reward = 10_000_000_000_000 * config.reward_numerator as u128 * 10 / config.reward_denumerator as u128;
+        let new_balance_reward = 30_000_000_000_000 * config.reward_numerator as u128 * 15 / config.reward_denumerator as u128;
+        let breakdown: RewardBreakdown = contract.get_reward_breakdown(accounts(0).to_string());
+        assert_eq!(breakdown.settled, U128(old_balance_reward));
+        assert_eq!(breakdown.live, U128(new_balance_reward));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_FOUND_ACCOUNT")]
+    fn unstake_unregistered_account_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(2));
+        context.attached_deposit(1);
+        testing_env!(context.build());
+        contract.unstake(U128(1));
+    }
+
+    #[test]
+    fn stake_spike_time_weighted_reward_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        context.epoch_height(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        // Spike stake 100x for one block before unstake
+        context.block_index(199);
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 1_000_000_000_000_000, None);
+
+        context.block_index(200);
+        testing_env!(context.build());
+        contract.internal_unstake(accounts(0).to_string(), 1_000_000_000_000_000);
+
+        let config = Config::default();
+        let time_weighted_reward = (10_000_000_000_000 * 199 + 1_010_000_000_000_000) * config.reward_numerator as u128 / config.reward_denumerator as u128;
+        let peak_reward = 1_010_000_000_000_000 * config.reward_numerator as u128 * 200 / config.reward_denumerator as u128;
+        let reward: Balance = contract.get_account_reward(accounts(0).to_string()).0;
+        assert_eq!(reward, time_weighted_reward);
+        assert!(reward < peak_reward / 50);
+    }
+
+    #[test]
+    fn owner_credit_rewards_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        context.attached_deposit(10u128.pow(24));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000);
+        contract.owner_credit_reward(accounts(0).to_string(), U128(500));
+
+        // Registered account is topped up, new accounts are registered
+        contract.owner_credit_rewards(vec![
+            (accounts(0).to_string(), U128(1_000)),
+            (accounts(2).to_string(), U128(2_000)),
+            (accounts(3).to_string(), U128(3_000))
+        ]);
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, 1_500);
+        assert_eq!(contract.get_account_reward(accounts(2).to_string()).0, 2_000);
+        assert_eq!(contract.get_account_reward(accounts(3).to_string()).0, 3_000);
+        assert_eq!(contract.pre_reward, 6_500);
+    }
+
+    #[test]
+    fn fee_on_transfer_token_deposit_test() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            fee_on_transfer_token: true,
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000);
+
+        // Token charge 1% fee, contract receive 9_900 of 10_000
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"10900\"".to_vec())]
+        );
+        let stake_action = TransferAction::Stake { lock_tier: None, referrer: None };
+        assert_eq!(contract.deposit_balance_callback(accounts(0).to_string(), U128(10_000), stake_action, U128(1_000), 0), U128(0));
+        assert_eq!(contract.get_account_info(accounts(0).to_string()).stake_balance, U128(9_900));
+        assert_eq!(contract.total_stake_balance, 9_900);
+        assert_eq!(contract.total_transfer_fee_deducted, 100);
+        assert!(get_logs().iter().any(|log| log.contains("\"event\":\"transfer_fee_deducted\"")));
+
+        // Failed balance check credit nothing and refund all
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(contract.deposit_balance_callback(accounts(0).to_string(), U128(10_000), TransferAction::FundReward, U128(10_900), 0), U128(10_000));
+        assert_eq!(contract.total_reward_fund, 1_000);
+    }
+
+    #[test]
+    fn fee_on_transfer_token_concurrent_transfer_test() {
+        let mut context = get_context(false);
+        context.attached_deposit(1);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new(accounts(1).to_string(), accounts(1).to_string(), Config {
+            fee_on_transfer_token: true,
+            ..Config::default()
+        }, 0, None);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 100_000);
+
+        // Balance is snapshot at ft_on_transfer, second deposit while first is measured is refunded
+        match contract.ft_on_transfer(accounts(0).to_string(), U128(10_000), "".to_string()) {
+            PromiseOrValue::Promise(_) => (),
+            PromiseOrValue::Value(_) => panic!("Expected balance check")
+        }
+        assert!(contract.fee_deposit_in_flight);
+        match contract.ft_on_transfer(accounts(0).to_string(), U128(10_000), "".to_string()) {
+            PromiseOrValue::Value(unused_amount) => assert_eq!(unused_amount, U128(10_000)),
+            PromiseOrValue::Promise(_) => panic!("Expected refund")
+        }
+        assert!(get_logs().iter().any(|log| log.contains("\"event\":\"deposit_deferred\"")));
+
+        // Withdraw reserve started before callback, balance no longer match snapshot, all amount refunded
+        contract.owner_withdraw_reserve(U128(50_000));
+        assert_eq!(contract.pending_ft_transfers, 1);
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"59900\"".to_vec())]
+        );
+        let stake_action = TransferAction::Stake { lock_tier: None, referrer: None };
+        assert_eq!(contract.deposit_balance_callback(accounts(0).to_string(), U128(10_000), stake_action, U128(100_000), 0), U128(10_000));
+        assert_eq!(contract.total_stake_balance, 0);
+        assert!(!contract.fee_deposit_in_flight);
+
+        // Deposit is refunded until withdraw callback settle
+        testing_env!(context.build());
+        match contract.ft_on_transfer(accounts(0).to_string(), U128(10_000), "".to_string()) {
+            PromiseOrValue::Value(unused_amount) => assert_eq!(unused_amount, U128(10_000)),
+            PromiseOrValue::Promise(_) => panic!("Expected refund")
+        }
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.withdraw_reserve_callback(U128(50_000));
+        assert_eq!(contract.pending_ft_transfers, 0);
+
+        // Nothing in flight, only balance increase of this transfer is credited
+        testing_env!(context.build());
+        match contract.ft_on_transfer(accounts(0).to_string(), U128(10_000), "".to_string()) {
+            PromiseOrValue::Promise(_) => (),
+            PromiseOrValue::Value(_) => panic!("Expected balance check")
+        }
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"59900\"".to_vec())]
+        );
+        let stake_action = TransferAction::Stake { lock_tier: None, referrer: None };
+        assert_eq!(contract.deposit_balance_callback(accounts(0).to_string(), U128(10_000), stake_action, U128(50_000), contract.ft_transfer_nonce), U128(0));
+        assert_eq!(contract.total_stake_balance, 9_900);
+        assert_eq!(contract.total_transfer_fee_deducted, 100);
+    }
+
+    #[test]
+    fn first_stake_accrue_from_stake_block_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+
+        // Register long before first stake
+        context.block_index(10);
+        testing_env!(context.build());
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.block_index(1_000);
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+        assert_eq!(account.last_block_balance_change, 1_000);
+        assert_eq!(account.pre_reward, 0);
+
+        context.block_index(1_100);
+        testing_env!(context.build());
+        let config = Config::default();
+        let expected_reward = 10_000_000_000_000 * config.reward_numerator as u128 * 100 / config.reward_denumerator as u128;
+        assert_eq!(contract.get_account_reward(accounts(0).to_string()).0, expected_reward);
+    }
+
+    #[test]
+    fn debug_reward_trace_test() {
+        let mut context = get_context(false);
+        context.block_index(0);
+        testing_env!(context.build());
+
+        let mut contract: StakingContract = StakingContract::new_default_config(accounts(1).to_string(), accounts(1).to_string(), 0);
+        contract.internal_create_account(accounts(0).to_string());
+
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        contract.internal_fund_reward(accounts(1).to_string(), 1_000_000_000_000);
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 10_000_000_000_000, None);
+
+        context.block_index(40);
+        testing_env!(context.build());
+        contract.internal_deposit_and_stake(accounts(0).to_string(), 5_000_000_000_000, None);
+
+        context.block_index(100);
+        testing_env!(context.build());
+        let trace: RewardTrace = contract.debug_reward_trace(accounts(0).to_string());
+        let account = Account::from(contract.accounts.get(&accounts(0).to_string()).unwrap());
+
+        assert_eq!(trace.computed_reward, U128(contract.internal_calculate_account_reward(&account)));
+        assert_eq!(trace.pre_reward, U128(account.pre_reward));
+        assert_eq!(trace.last_block_balance_change, 40);
+        assert_eq!(trace.current_block, 100);
+        assert_eq!(trace.diff_block, 60);
+        assert_eq!(trace.stake_balance, U128(15_000_000_000_000));
+        assert_eq!(trace.computed_reward.0, trace.stake_balance.0 * trace.numerator as u128 * trace.diff_block as u128 / trace.denumerator as u128);
+    }
+}
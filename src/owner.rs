@@ -0,0 +1,440 @@
+use near_sdk::serde_json::json;
+
+use crate::*;
+
+#[near_bindgen]
+impl StakingContract {
+
+    /**
+     * Owner set lock tiers user can choose when stake
+     * Tier index in msg of ft_on_transfer is index of this list
+     */
+    pub fn set_lock_tiers(&mut self, lock_tiers: Vec<LockTier>) {
+        self.assert_owner();
+        assert!(lock_tiers.iter().all(|tier| tier.boost_basis_points >= BASIS_POINTS), "{}", ContractError::InvalidLockTier.as_ref());
+
+        self.lock_tiers = lock_tiers;
+    }
+
+    /**
+     * Owner update reward config
+     * Settle global reward with old config before apply new config
+     */
+    pub fn update_config(&mut self, config: Config) {
+        self.assert_owner();
+        config.assert_valid();
+        check_apr_consistency(&config, self.strict_config);
+        // Stored accrual points are in unit of time basis, can't switch after reward start
+        assert!(
+            config.time_basis == self.config.time_basis || self.reward_start_block.is_none(),
+            "{}", ContractError::InvalidConfig.as_ref()
+        );
+        // Stored reward remainders are scaled by multiplier, it is fixed after init
+        assert_eq!(
+            config.reward_precision_multiplier, self.config.reward_precision_multiplier,
+            "{}", ContractError::InvalidConfig.as_ref()
+        );
+        // Settlement walk config history, owner_settle_all sweep prune it
+        assert!(self.config_history.len() < MAX_CONFIG_HISTORY, "{}", ContractError::ConfigHistoryFull.as_ref());
+
+        self.internal_settle_global_reward();
+
+        // Account not settled since this point accrue earlier blocks at rate of config history
+        self.config = config;
+        self.config_history.push(&(self.internal_accrual_clock(), config));
+    }
+
+    /**
+     * Owner enable rejecting config with total_apr not matching reward numerator and denumerator
+     */
+    pub fn set_strict_config(&mut self, strict_config: bool) {
+        self.assert_owner();
+
+        self.strict_config = strict_config;
+    }
+
+    /**
+     * Owner set reward rate by target APR, numerator and denumerator are derived with units per year of time basis
+     * Rate per block = apr_basis_points / (BASIS_POINTS * BLOCKS_PER_YEAR)
+     */
+    pub fn set_apr(&mut self, apr_basis_points: u32) {
+        self.assert_owner();
+
+        let config: Config = Config {
+            reward_numerator: apr_basis_points,
+            reward_denumerator: u64::from(BASIS_POINTS) * self.config.units_per_year(),
+            total_apr: apr_basis_points / 100,
+            apr_basis_points: Some(apr_basis_points),
+            ..self.config
+        };
+        self.update_config(config);
+    }
+
+    /**
+     * Owner snapshot global reward into pre_reward, before config change or pause
+     */
+    pub fn owner_settle_global(&mut self) {
+        self.assert_owner();
+
+        self.internal_settle_global_reward();
+    }
+
+    /**
+     * Owner settle accounts in index range [from_index, from_index + limit) before config change
+     * Settled accounts accrue only at new rate after update_config, return number of settled accounts
+     * Contiguous batches from index 0 to last account prune config history before sweep start
+     */
+    pub fn owner_settle_all(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_owner();
+        assert_batch_len(limit as usize);
+
+        // Sweep start from index 0, every account settled after it don't need config history before sweep start
+        if from_index == 0 {
+            self.settle_sweep_start = self.internal_accrual_clock();
+            self.settle_sweep_next_index = 0;
+        }
+
+        let account_ids = self.accounts.keys_as_vector();
+        let account_ids: Vec<AccountId> = (from_index..std::cmp::min(from_index + limit, account_ids.len()))
+            .map(|index| account_ids.get(index).unwrap())
+            .collect();
+        for account_id in account_ids.iter() {
+            let mut account: Account = self.internal_get_account(account_id).unwrap();
+            self.internal_settle_account_reward(&mut account);
+            self.accounts.insert(account_id, &UpgradableAccount::from(account));
+        }
+
+        // Only contiguous batches advance sweep
+        if from_index == self.settle_sweep_next_index {
+            self.settle_sweep_next_index = from_index + account_ids.len() as u64;
+            if self.settle_sweep_next_index >= self.accounts.len() {
+                self.internal_prune_config_history(self.settle_sweep_start);
+            }
+        }
+
+        account_ids.len() as u64
+    }
+
+    /**
+     * Break-glass: owner set global pre_reward and last change block explicitly
+     * Only use to fix inconsistent reward state after migration or bug, old and new values are logged for audit
+     */
+    pub fn owner_recalibrate_global(&mut self, new_pre_reward: U128, block: BlockHeight) {
+        self.assert_owner();
+
+        emit_event("recalibrate_global", json!({
+            "old_pre_reward": U128(self.pre_reward),
+            "old_last_block_balance_change": self.last_block_balance_change,
+            "new_pre_reward": new_pre_reward,
+            "new_last_block_balance_change": block
+        }));
+
+        self.pre_reward = new_pre_reward.0;
+        self.last_block_balance_change = block;
+    }
+
+    /**
+     * Owner log global reward state as checkpoint, indexers interpolate reward between checkpoints
+     * pre_reward include reward accrued since last global change, state is not changed
+     */
+    pub fn checkpoint_reward(&mut self) {
+        self.assert_owner();
+
+        emit_event("checkpoint_reward", json!({
+            "block": env::block_index(),
+            "total_stake_balance": U128(self.total_stake_balance),
+            "pre_reward": U128(self.pre_reward + self.internal_calculate_global_reward()),
+            "config": self.config
+        }));
+    }
+
+    /**
+     * Owner fix wrong FT contract of new deployment, only allowed while no stake, unstake balance or unpaid reward reserve in pool
+     * Reserve funded in old token must be withdrawn first, it can't be paid in new token
+     */
+    pub fn set_ft_contract_id(&mut self, ft_contract_id: AccountId) {
+        self.assert_owner();
+        assert!(
+            self.total_stake_balance == 0 && self.total_unstake_balance == 0
+                && self.total_reward_fund.saturating_sub(self.total_paid_reward_balance) == 0,
+            "{}", ContractError::PoolNotEmpty.as_ref()
+        );
+
+        emit_event("set_ft_contract_id", json!({
+            "old_ft_contract_id": self.ft_contract_id,
+            "new_ft_contract_id": ft_contract_id
+        }));
+
+        self.ft_contract_id = ft_contract_id;
+    }
+
+    /**
+     * Owner slash part of account stake, slashed token is moved to treasury if set, otherwise to reward reserve
+     * Settle account and global reward with stake before slash, reserve must stay solvent before sending to treasury
+     */
+    pub fn slash(&mut self, account_id: AccountId, amount: U128, reason: String) {
+        self.assert_owner();
+        let mut account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+        assert!(!account.operation_in_flight, "{}", ContractError::OperationInFlight.as_ref());
+        assert!(amount.0 <= account.stake_balance, "{}", ContractError::AmountExceedsBalance.as_ref());
+
+        self.internal_settle_account_reward(&mut account);
+        account.stake_balance -= amount.0;
+        account.locked_balance = std::cmp::min(account.locked_balance, account.stake_balance);
+        if account.stake_balance == 0 && amount.0 > 0 {
+            self.total_staker -= 1;
+        }
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+
+        self.pre_reward += self.internal_calculate_global_reward();
+        self.total_stake_balance -= amount.0;
+        self.last_block_balance_change = self.internal_accrual_clock();
+        self.total_reward_fund += amount.0;
+        if self.treasury_id.is_some() {
+            self.assert_solvent_after(amount.0);
+        }
+        self.internal_transfer_to_treasury(amount.0);
+
+        emit_event("slash", json!({
+            "account_id": account_id,
+            "amount": amount,
+            "reason": reason
+        }));
+    }
+
+    /**
+     * Owner credit reward to account without stake, account is registered if needed and storage is paid by attached deposit
+     * Credit is added to global pre_reward so it count as outstanding reward, reward reserve must cover it
+     */
+    #[payable]
+    pub fn owner_credit_reward(&mut self, account_id: AccountId, amount: U128) {
+        self.owner_credit_rewards(vec![(account_id, amount)]);
+    }
+
+    /**
+     * Owner credit reward to many accounts in one call, same as owner_credit_reward for each entry
+     * Reward reserve must cover total credited, storage of new accounts is paid by attached deposit
+     */
+    #[payable]
+    pub fn owner_credit_rewards(&mut self, entries: Vec<(AccountId, U128)>) {
+        self.assert_owner();
+        assert_at_least_one_yocto();
+        assert_batch_len(entries.len());
+
+        let before_storage_usage = env::storage_usage();
+        for (account_id, amount) in entries {
+            if self.accounts.get(&account_id).is_none() {
+                self.internal_create_account(account_id.clone());
+            }
+            let mut account: Account = self.internal_get_account(&account_id).unwrap();
+            account.pre_reward += amount.0;
+            self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+            self.pre_reward += amount.0;
+
+            emit_event("credit_reward", json!({
+                "account_id": account_id,
+                "amount": amount
+            }));
+        }
+        let after_storage_usage = env::storage_usage();
+
+        self.assert_solvent_after(0);
+        refund_deposit(after_storage_usage.saturating_sub(before_storage_usage));
+    }
+
+    /**
+     * Full internal account state for debugging accrual, owner only
+     */
+    pub fn debug_get_account(&self, account_id: AccountId) -> AccountDebug {
+        self.assert_owner();
+
+        AccountDebug::from(Account::from(self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref())))
+    }
+
+    /**
+     * Every input of account reward formula with computed reward at current block, owner only
+     */
+    pub fn debug_reward_trace(&self, account_id: AccountId) -> RewardTrace {
+        self.assert_owner();
+        let account: Account = Account::from(self.accounts.get(&account_id).expect(ContractError::NotFoundAccount.as_ref()));
+
+        RewardTrace {
+            last_block_balance_change: account.last_block_balance_change,
+            current_block: self.internal_accrual_clock(),
+            diff_block: self.effective_diff_block(account.last_block_balance_change),
+            stake_balance: U128(account.stake_balance),
+            numerator: self.config.reward_numerator,
+            denumerator: self.config.reward_denumerator,
+            computed_reward: U128(self.internal_calculate_account_reward(&account)),
+            pre_reward: U128(account.pre_reward)
+        }
+    }
+
+    /**
+     * Owner freeze account for compliance hold
+     * Settle reward to freeze block, frozen account stop accrue reward
+     */
+    pub fn freeze_account(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        let mut account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+
+        self.internal_settle_account_reward(&mut account);
+        account.frozen = true;
+
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+    }
+
+    /**
+     * Owner unfreeze account, reward accrue again from unfreeze block
+     */
+    pub fn unfreeze_account(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        let mut account: Account = self.internal_get_account(&account_id).expect(ContractError::NotFoundAccount.as_ref());
+
+        account.last_block_balance_change = self.internal_accrual_clock();
+        account.frozen = false;
+
+        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+    }
+
+    /**
+     * Owner pause staking pool, reward stop accrue at pause block
+     */
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        assert!(!self.paused, "{}", ContractError::ContractPaused.as_ref());
+
+        self.paused = true;
+        self.paused_in_block = self.config.reward_clock();
+    }
+
+    /**
+     * Owner resume staking pool
+     * Settle global reward to pause block, paused blocks are added to total_paused_blocks so accrual clock continue from pause point
+     */
+    pub fn resume(&mut self) {
+        self.assert_owner();
+        assert!(self.paused, "{}", ContractError::ContractNotPaused.as_ref());
+
+        self.internal_settle_global_reward();
+        self.total_paused_blocks += self.config.reward_clock().saturating_sub(self.paused_in_block);
+        self.paused = false;
+    }
+
+    /**
+     * One time migrate declared APR from legacy percent to basis points
+     * No-op if config already have apr_basis_points
+     */
+    pub fn migrate_apr_to_basis_points(&mut self) {
+        self.assert_owner();
+
+        if self.config.apr_basis_points.is_none() {
+            self.config.apr_basis_points = Some(self.config.total_apr * 100);
+        }
+    }
+
+    /**
+     * Owner set max registered accounts, existing accounts over new cap are kept
+     */
+    pub fn set_max_accounts(&mut self, max_accounts: Option<u64>) {
+        self.assert_owner();
+
+        self.config.max_accounts = max_accounts;
+    }
+
+    /**
+     * Owner set wrap NEAR contract, harvest can unwrap reward only if FT contract is this contract
+     */
+    pub fn set_wrap_near_contract_id(&mut self, wrap_near_contract_id: Option<AccountId>) {
+        self.assert_owner();
+
+        self.wrap_near_contract_id = wrap_near_contract_id;
+    }
+
+    /**
+     * Owner set token user deposit with {"action":"boost"} to boost reward weight
+     */
+    pub fn set_boost_ft_contract_id(&mut self, boost_ft_contract_id: Option<AccountId>) {
+        self.assert_owner();
+
+        self.boost_ft_contract_id = boost_ft_contract_id;
+    }
+
+    /**
+     * Owner withdraw collected NEAR registration fee, return withdrawn amount
+     */
+    #[payable]
+    pub fn owner_withdraw_registration_fee(&mut self) -> U128 {
+        assert_one_yocto();
+        self.assert_owner();
+
+        let amount: Balance = self.total_registration_fee;
+        if amount > 0 {
+            self.total_registration_fee = 0;
+            Promise::new(self.owner_id.clone()).transfer(amount);
+        }
+
+        U128(amount)
+    }
+
+    /**
+     * Owner set treasury receiving harvest fee and slashed token, None keep them in reward reserve
+     */
+    pub fn set_treasury_id(&mut self, treasury_id: Option<AccountId>) {
+        self.assert_owner();
+
+        self.treasury_id = treasury_id;
+    }
+
+    /**
+     * Owner set memo of harvest and withdraw ft_transfer
+     */
+    pub fn set_transfer_memos(&mut self, harvest_memo: String, withdraw_memo: String) {
+        self.assert_owner();
+
+        self.harvest_memo = harvest_memo;
+        self.withdraw_memo = withdraw_memo;
+    }
+
+    /**
+     * Owner limit reward harvested by all accounts per window, None disable limit
+     */
+    pub fn set_max_harvest_per_window(&mut self, max_harvest_per_window: Option<U128>) {
+        self.assert_owner();
+
+        self.max_harvest_per_window = max_harvest_per_window.map(|amount| amount.0);
+    }
+
+    /**
+     * Owner set length of harvest window, current window restart at this block
+     */
+    pub fn set_harvest_window_blocks(&mut self, harvest_window_blocks: BlockHeight) {
+        self.assert_owner();
+        assert!(harvest_window_blocks > 0, "{}", ContractError::InvalidConfig.as_ref());
+
+        self.harvest_window_blocks = harvest_window_blocks;
+        self.harvest_window_start = env::block_index();
+        self.harvested_in_window = 0;
+    }
+
+    pub fn add_harvest_receiver(&mut self, receiver_id: AccountId) {
+        self.assert_owner();
+
+        self.harvest_receiver_allowlist.insert(&receiver_id);
+    }
+
+    pub fn remove_harvest_receiver(&mut self, receiver_id: AccountId) {
+        self.assert_owner();
+
+        self.harvest_receiver_allowlist.remove(&receiver_id);
+    }
+
+    /**
+     * Owner enable or disable harvest receiver allowlist for harvest_to
+     */
+    pub fn set_harvest_receiver_allowlist_enabled(&mut self, enabled: bool) {
+        self.assert_owner();
+
+        self.harvest_receiver_allowlist_enabled = enabled;
+    }
+}
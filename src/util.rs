@@ -1,3 +1,5 @@
+use near_sdk::serde_json::json;
+
 use crate::*;
 
 pub(crate) fn assert_one_yocto() {
@@ -10,18 +12,121 @@ pub(crate) fn assert_at_least_one_yocto() {
     "Require attached deposit of at least 1 yoctoNear")
 }
 
-pub(crate) fn refund_deposit(storage_used: u64) {
-    let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
+/**
+ * Reject init when contract state already exist
+ * #[init] wrapper check it too, explicit check keep state safe if init is reached after migrate in same batch
+ */
+pub(crate) fn assert_not_initialized() {
+    assert!(!env::state_exists(), "{}", ContractError::AlreadyInitialized.as_ref());
+}
+
+/**
+ * Reject Vec input longer than MAX_BATCH before loop, so call can't run out of gas with partial state
+ */
+pub(crate) fn assert_batch_len(len: usize) {
+    assert!(len <= MAX_BATCH, "{}", ContractError::BatchTooLarge.as_ref());
+}
+
+/**
+ * Check APR derived from reward numerator and denumerator is within APR_TOLERANCE_BASIS_POINTS of declared total_apr
+ * Strict mode reject mismatch, otherwise mismatch is logged as event
+ */
+pub(crate) fn check_apr_consistency(config: &Config, strict: bool) {
+    let derived_apr: U256 = U256::from(config.reward_numerator) * U256::from(config.units_per_year()) * U256::from(BASIS_POINTS) / U256::from(config.reward_denumerator);
+    let declared_apr: U256 = U256::from(config.total_apr) * U256::from(100);
+    let diff: U256 = if derived_apr > declared_apr { derived_apr - declared_apr } else { declared_apr - derived_apr };
+    if diff <= U256::from(APR_TOLERANCE_BASIS_POINTS) {
+        return;
+    }
+
+    assert!(!strict, "{}", ContractError::AprMismatch.as_ref());
+    emit_event("apr_mismatch", json!({
+        "total_apr": config.total_apr,
+        "derived_apr_basis_points": derived_apr.to_string()
+    }));
+}
+
+/**
+ * Refund attached deposit not used for storage, return refund amount
+ */
+pub(crate) fn refund_deposit(storage_used: u64) -> Balance {
+    refund_deposit_with_fee(storage_used, 0)
+}
+
+/**
+ * Refund attached deposit not used for storage and fee, fee is kept by contract
+ */
+pub(crate) fn refund_deposit_with_fee(storage_used: u64, fee: Balance) -> Balance {
+    let required_cost = env::storage_byte_cost() * Balance::from(storage_used) + fee;
     let attached_deposit = env::attached_deposit();
 
     assert!(
         required_cost <= attached_deposit,
-        "Must attach {} yoctoNear to cover storage", required_cost
+        "Must attach {} yoctoNear to cover storage and registration fee", required_cost
     );
 
     let refund = attached_deposit - required_cost;
 
-    if refund > 1 {
+    if refund > 0 {
         Promise::new(env::predecessor_account_id()).transfer(refund);
     }
-}
\ No newline at end of file
+
+    refund
+}
+
+/**
+ * Format raw token amount to decimal string, ex: 12500000 with 6 decimals is "12.5"
+ */
+pub(crate) fn format_decimal(amount: Balance, decimals: u8) -> String {
+    let unit: Balance = 10u128.pow(decimals as u32);
+    let integer_part = amount / unit;
+    let fraction_part = amount % unit;
+
+    if fraction_part == 0 {
+        return integer_part.to_string();
+    }
+
+    let fraction = format!("{:0width$}", fraction_part, width = decimals as usize);
+    format!("{}.{}", integer_part, fraction.trim_end_matches('0'))
+}
+
+/**
+ * Reward formula: stake_balance * numerator * diff_block * multiplier / denumerator
+ * Panic with ERR_REWARD_OVERFLOW if product overflow 256 bits
+ */
+pub(crate) fn calculate_reward(stake_balance: Balance, numerator: u32, diff_block: BlockHeight, multiplier: u64, denumerator: u64) -> U256 {
+    U256::from(stake_balance)
+        .checked_mul(U256::from(numerator))
+        .and_then(|value| value.checked_mul(U256::from(diff_block)))
+        .and_then(|value| value.checked_mul(U256::from(multiplier)))
+        .expect(ContractError::RewardOverflow.as_ref())
+        / U256::from(denumerator)
+}
+
+/**
+ * APY in basis points of APR compounded periods times per year: (1 + apr / periods) ^ periods - 1
+ * Fixed point with COMPOUND_PRECISION, power by squaring so per block compounding stay cheap
+ */
+pub(crate) fn compound_apy_basis_points(apr_basis_points: u32, periods: u64) -> u32 {
+    let precision: U256 = U256::from(COMPOUND_PRECISION);
+    let mut base: U256 = precision + U256::from(apr_basis_points) * precision / (U256::from(BASIS_POINTS) * U256::from(periods));
+    let mut exponent: u64 = periods;
+    let mut result: U256 = precision;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base / precision;
+        }
+        base = base * base / precision;
+        exponent >>= 1;
+    }
+
+    ((result - precision) * U256::from(BASIS_POINTS) / precision).as_u32()
+}
+
+/**
+ * Convert U256 to Balance, panic with ERR_REWARD_OVERFLOW instead of truncate
+ */
+pub(crate) fn u256_to_balance(value: U256) -> Balance {
+    assert!(value <= U256::from(u128::MAX), "{}", ContractError::RewardOverflow.as_ref());
+    value.as_u128()
+}
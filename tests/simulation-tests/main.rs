@@ -1,18 +1,44 @@
-use near_sdk::{serde_json::json, json_types::U128};
+use near_sdk::{serde_json::{json, Value}, json_types::U128};
 use near_sdk_sim::{init_simulator, UserAccount, DEFAULT_GAS, STORAGE_AMOUNT, to_yocto};
 use near_sdk_sim::transaction::ExecutionStatus;
-use staking_contract::AccountJson;
+use staking_contract::{AccountJson, PoolInfo, TransferResult, TransferKind};
+use std::path::Path;
+use std::process::Command;
+use std::sync::Once;
 
 near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
-    FT_CONTRACT_WASM_FILE => "token-test/vbi-ft.wasm",
-    STAKING_CONTRACT_WASM_FILE => "out/staking-contract.wasm"
+    FT_CONTRACT_WASM_FILE => "token-test/vbi-ft.wasm"
+}
+
+const MOCK_FT_CONTRACT_WASM_FILE: &str = "token-test/mock-ft.wasm";
+const STAKING_CONTRACT_WASM_FILE: &str = "out/staking-contract.wasm";
+
+static BUILD_WASM: Once = Once::new();
+
+/**
+ * Wasm built by build.sh from current source, built once per test run so simulation never load stale artifact
+ */
+pub fn built_wasm(path: &str) -> Vec<u8> {
+    let manifest_dir: &Path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    BUILD_WASM.call_once(|| {
+        let status = Command::new("bash")
+            .arg("build.sh")
+            .current_dir(manifest_dir)
+            .status()
+            .expect("Failed to run build.sh");
+        assert!(status.success(), "build.sh failed");
+    });
+
+    std::fs::read(manifest_dir.join(path)).expect("Wasm not found after build.sh")
 }
 
 const FT_CONTRACT_ID: &str = "ft_contract";
+const FT_DECIMALS: u8 = 18;
 const FT_TOTAL_SUPPY: &str = "100000000000000000000000000000";
 const FT_STAKING_CONTRACT_BALANCE: &str = "50000000000000000000000000000";
 const STAKING_CONTRACT_ID: &str = "staking_contract";
 const ALICE_DEPOSIT_BALANCE: &str = "10000000000000000000000000000";
+const MAX_STAKE_PER_ACCOUNT: &str = "4000000000000000000000000000";
 
 pub fn init() -> (UserAccount, UserAccount, UserAccount, UserAccount) {
     let root = init_simulator(None);
@@ -34,12 +60,13 @@ pub fn init() -> (UserAccount, UserAccount, UserAccount, UserAccount) {
 
     // Deploy and init staking contract
     let staking_contract = root.deploy_and_init(
-        &STAKING_CONTRACT_WASM_FILE, 
+        &built_wasm(STAKING_CONTRACT_WASM_FILE), 
         STAKING_CONTRACT_ID.to_string(), 
         "new_default_config", 
         &json!({
             "owner_id": alice.account_id(),
-            "ft_contract_id": ft_contract.account_id()
+            "ft_contract_id": ft_contract.account_id(),
+            "ft_decimals": FT_DECIMALS
         }).to_string().as_bytes(), 
         STORAGE_AMOUNT, 
         DEFAULT_GAS
@@ -56,13 +83,14 @@ pub fn init() -> (UserAccount, UserAccount, UserAccount, UserAccount) {
         to_yocto("0.01")
     );
 
-    // Transfer 50% total supply to staking contract
+    // Transfer 50% total supply to staking contract to fund reward
     alice.call(
         ft_contract.account_id(), 
-        "ft_transfer", 
+        "ft_transfer_call", 
         &json!({
             "receiver_id": staking_contract.account_id(),
-            "amount": FT_STAKING_CONTRACT_BALANCE
+            "amount": FT_STAKING_CONTRACT_BALANCE,
+            "msg": "{\"action\":\"fund_reward\"}"
         }).to_string().as_bytes(), 
         DEFAULT_GAS, 
         1
@@ -72,6 +100,126 @@ pub fn init() -> (UserAccount, UserAccount, UserAccount, UserAccount) {
 }
 
 
+/**
+ * Same as init with mock FT charging fee_basis_points of every transfer, staking config is passed to new
+ */
+pub fn init_mock_ft(fee_basis_points: u32, config: Value) -> (UserAccount, UserAccount, UserAccount, UserAccount) {
+    let root = init_simulator(None);
+
+    let alice = root.create_user("alice".to_string(), to_yocto("100"));
+
+    let ft_contract = root.deploy_and_init(
+        &built_wasm(MOCK_FT_CONTRACT_WASM_FILE),
+        FT_CONTRACT_ID.to_string(), 
+        "new",
+        &json!({
+            "owner_id": alice.account_id(),
+            "total_supply": FT_TOTAL_SUPPY,
+            "fee_basis_points": fee_basis_points
+        }).to_string().as_bytes(),
+        STORAGE_AMOUNT,
+        DEFAULT_GAS
+    );
+
+    let staking_contract = root.deploy_and_init(
+        &built_wasm(STAKING_CONTRACT_WASM_FILE), 
+        STAKING_CONTRACT_ID.to_string(), 
+        "new", 
+        &json!({
+            "owner_id": alice.account_id(),
+            "ft_contract_id": ft_contract.account_id(),
+            "config": config,
+            "ft_decimals": FT_DECIMALS
+        }).to_string().as_bytes(), 
+        STORAGE_AMOUNT, 
+        DEFAULT_GAS
+    );
+
+    root.call(
+        ft_contract.account_id(), 
+        "storage_deposit", 
+        &json!({
+            "account_id": staking_contract.account_id()
+        }).to_string().as_bytes(), 
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": FT_STAKING_CONTRACT_BALANCE,
+            "msg": "{\"action\":\"fund_reward\"}"
+        }).to_string().as_bytes(), 
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    (root, ft_contract, staking_contract, alice)
+}
+
+pub fn default_config() -> Value {
+    json!({
+        "reward_numerator": 715,
+        "reward_denumerator": 100000000000u64,
+        "total_apr": 15,
+        "min_staking_amount": "1000000000000000000",
+        "reward_precision_multiplier": 1,
+        "max_stake_per_account": null,
+        "harvest_cooldown_blocks": null,
+        "min_unstake_amount": "0",
+        "apr_basis_points": 1500,
+        "referral_bonus_bp": 0,
+        "harvest_fee_basis_points": 0,
+        "epoch_duration_seconds": null,
+        "accrue_on_unstaking": false,
+        "reduced_rate_bp": 0,
+        "estimated_blocks_per_epoch": null,
+        "max_token_boost_bp": 0,
+        "max_accrued_reward_per_account": null,
+        "registration_fee": "0",
+        "time_basis": "per_block",
+        "max_accounts": null,
+        "permissioned_registration": false,
+        "vesting_blocks": null,
+        "fee_on_transfer_token": false
+    })
+}
+
+pub fn update_config(staking_contract: &UserAccount, owner: &UserAccount, config: Value) {
+    owner.call(
+        staking_contract.account_id(), 
+        "update_config", 
+        &json!({
+            "config": config
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+}
+
+pub fn produce_blocks(root: &UserAccount, num_blocks: u64) {
+    root.borrow_runtime_mut().produce_blocks(num_blocks).unwrap();
+}
+
+pub fn produce_epochs(root: &UserAccount, num_epochs: u64) {
+    let epoch_length = root.borrow_runtime().genesis.epoch_length;
+    produce_blocks(root, epoch_length * num_epochs);
+}
+
+pub fn ft_balance_of(root: &UserAccount, ft_contract: &UserAccount, account_id: String) -> u128 {
+    let balance: U128 = root.view(
+        ft_contract.account_id(), 
+        "ft_balance_of", 
+        &json!({
+            "account_id": account_id
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    balance.0
+}
+
 #[test]
 fn init_contract_test() {
     let (root, ft_contract, staking_contract, alice) = init();
@@ -179,4 +327,1452 @@ fn deposit_and_stake_error_storage_test() {
         unreachable!();
     }
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn stake_with_lock_tier_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+    let bob = root.create_user("bob".to_string(), to_yocto("100"));
+
+    // Owner set lock tiers
+    alice.call(
+        staking_contract.account_id(), 
+        "set_lock_tiers", 
+        &json!({
+            "lock_tiers": [
+                { "lock_epochs": 10, "boost_basis_points": 12000 },
+                { "lock_epochs": 20, "boost_basis_points": 15000 },
+                { "lock_epochs": 30, "boost_basis_points": 20000 }
+            ]
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+
+    // Bob get token to stake
+    root.call(
+        ft_contract.account_id(), 
+        "storage_deposit", 
+        &json!({
+            "account_id": bob.account_id()
+        }).to_string().as_bytes(), 
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    );
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer", 
+        &json!({
+            "receiver_id": bob.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE
+        }).to_string().as_bytes(), 
+        DEFAULT_GAS, 
+        1
+    );
+
+    for user in [&alice, &bob].iter() {
+        user.call(
+            staking_contract.account_id(), 
+            "storage_deposit", 
+            &json!({}).to_string().as_bytes(),
+            DEFAULT_GAS, 
+            to_yocto("0.01")
+        );
+    }
+
+    // Alice stake with tier 2, bob stake without lock
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": "{\"action\":\"stake\",\"lock_tier\":2}"
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    bob.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+
+    let alice_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    let bob_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": bob.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+
+    assert_eq!(alice_json.lock_tier, Some(2));
+    assert!(alice_json.lock_until_epoch > alice_json.current_epoch);
+    assert!(alice_json.reward.0 > bob_json.reward.0);
+
+    // Alice can't unstake before lock end
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "unstake", 
+        &json!({
+            "amount": ALICE_DEPOSIT_BALANCE
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+
+    if let ExecutionStatus::Failure(error) = &outcome.status() {
+        assert!(error.to_string().contains("ERR_STAKE_LOCKED"));
+    } else {
+        unreachable!();
+    }
+}
+
+#[test]
+fn exit_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    );
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+
+    let balance_before_exit: U128 = root.view(
+        ft_contract.account_id(), 
+        "ft_balance_of", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "exit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+
+    let harvested_reward: U128 = outcome.unwrap_json::<TransferResult>().amount;
+    assert!(harvested_reward.0 > 0);
+
+    // Reward transferred to alice
+    let balance_after_exit: U128 = root.view(
+        ft_contract.account_id(), 
+        "ft_balance_of", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(balance_after_exit.0, balance_before_exit.0 + harvested_reward.0);
+
+    // All stake moved to unstake
+    let account_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(account_json.stake_balance.0, 0);
+    assert_eq!(account_json.unstake_balance, U128(10000000000000000000000000000));
+    assert_eq!(account_json.reward.0, 0);
+}
+
+#[test]
+fn deposit_over_max_stake_per_account_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+
+    // Owner set max stake per account
+    let mut config = default_config();
+    config["max_stake_per_account"] = json!(MAX_STAKE_PER_ACCOUNT);
+    update_config(&staking_contract, &alice, config);
+
+    let pool_info: PoolInfo = root.view(
+        staking_contract.account_id(), 
+        "get_pool_info", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(pool_info.max_stake_per_account, Some(U128(MAX_STAKE_PER_ACCOUNT.parse().unwrap())));
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    );
+
+    let balance_before: U128 = root.view(
+        ft_contract.account_id(), 
+        "ft_balance_of", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+
+    // Only max stake is staked, overflow bounce back to alice
+    let account_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(account_json.stake_balance, U128(MAX_STAKE_PER_ACCOUNT.parse().unwrap()));
+
+    let balance_after: U128 = root.view(
+        ft_contract.account_id(), 
+        "ft_balance_of", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(balance_before.0 - balance_after.0, MAX_STAKE_PER_ACCOUNT.parse::<u128>().unwrap());
+}
+
+#[test]
+fn referral_bonus_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+    let carol = root.create_user("carol".to_string(), to_yocto("100"));
+
+    // Owner set referral bonus 10%
+    let mut config = default_config();
+    config["referral_bonus_bp"] = json!(1000);
+    update_config(&staking_contract, &alice, config);
+
+    for user in [&alice, &carol].iter() {
+        user.call(
+            staking_contract.account_id(), 
+            "storage_deposit", 
+            &json!({}).to_string().as_bytes(),
+            DEFAULT_GAS, 
+            to_yocto("0.01")
+        );
+    }
+
+    // Alice stake with carol as referrer, then stake more to settle reward
+    for _ in 0..2 {
+        alice.call(
+            ft_contract.account_id(), 
+            "ft_transfer_call", 
+            &json!({
+                "receiver_id": staking_contract.account_id(),
+                "amount": ALICE_DEPOSIT_BALANCE,
+                "msg": "{\"action\":\"stake\",\"referrer\":\"carol\"}"
+            }).to_string().as_bytes(),
+            DEFAULT_GAS, 
+            1
+        );
+    }
+
+    let carol_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": carol.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+
+    assert_eq!(carol_json.stake_balance.0, 0);
+    assert!(carol_json.reward.0 > 0);
+}
+
+#[test]
+fn harvest_fee_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+
+    // Owner set harvest fee 10%
+    let mut config = default_config();
+    config["harvest_fee_basis_points"] = json!(1000);
+    update_config(&staking_contract, &alice, config);
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    );
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+
+    let balance_before: U128 = root.view(
+        ft_contract.account_id(), 
+        "ft_balance_of", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let net_reward: U128 = outcome.unwrap_json::<TransferResult>().amount;
+
+    // Alice receive net reward, fee is kept in contract
+    let balance_after: U128 = root.view(
+        ft_contract.account_id(), 
+        "ft_balance_of", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(balance_after.0 - balance_before.0, net_reward.0);
+
+    let pool_info: PoolInfo = root.view(
+        staking_contract.account_id(), 
+        "get_pool_info", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(pool_info.harvest_fee_basis_points, 1000);
+    assert!(pool_info.total_fee_collected.0 > 0);
+    assert_eq!(pool_info.total_fee_collected.0, (net_reward.0 + pool_info.total_fee_collected.0) / 10);
+}
+
+#[test]
+fn harvest_result_json_test() {
+    let (_root, ft_contract, staking_contract, alice) = init();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    );
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+
+    // Raw JSON shape for front-end
+    let result_json: Value = outcome.unwrap_json();
+    assert_eq!(result_json["account_id"], json!(alice.account_id()));
+    assert_eq!(result_json["kind"], json!("harvest"));
+    assert!(result_json["amount"].is_string());
+
+    let result: TransferResult = outcome.unwrap_json();
+    assert_eq!(result.kind, TransferKind::Harvest);
+    assert!(result.amount.0 > 0);
+}
+
+#[test]
+fn check_ft_registration_test() {
+    let (_root, _ft_contract, staking_contract, alice) = init();
+
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "check_ft_registration", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    );
+    outcome.assert_success();
+
+    // Staking contract is registered in init
+    let is_registered: bool = outcome.unwrap_json();
+    assert!(is_registered);
+}
+
+#[test]
+fn boost_token_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+    let bob = root.create_user("bob".to_string(), to_yocto("100"));
+
+    // Deploy boost token, alice own total supply
+    let boost_contract = root.deploy_and_init(
+        &FT_CONTRACT_WASM_FILE,
+        "boost_contract".to_string(), 
+        "new_default_meta",
+        &json!({
+            "owner_id": alice.account_id(),
+            "total_supply": FT_TOTAL_SUPPY
+        }).to_string().as_bytes(),
+        STORAGE_AMOUNT,
+        DEFAULT_GAS
+    );
+    root.call(
+        boost_contract.account_id(), 
+        "storage_deposit", 
+        &json!({
+            "account_id": staking_contract.account_id()
+        }).to_string().as_bytes(), 
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    // Owner enable boost up to 1x extra weight
+    let mut config = default_config();
+    config["max_token_boost_bp"] = json!(10000);
+    update_config(&staking_contract, &alice, config);
+    alice.call(
+        staking_contract.account_id(), 
+        "set_boost_ft_contract_id", 
+        &json!({
+            "boost_ft_contract_id": boost_contract.account_id()
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+
+    // Give bob same stake amount as alice
+    root.call(
+        ft_contract.account_id(), 
+        "storage_deposit", 
+        &json!({
+            "account_id": bob.account_id()
+        }).to_string().as_bytes(), 
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer", 
+        &json!({
+            "receiver_id": bob.account_id(),
+            "amount": MAX_STAKE_PER_ACCOUNT
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    for user in [&alice, &bob].iter() {
+        user.call(
+            staking_contract.account_id(), 
+            "storage_deposit", 
+            &json!({}).to_string().as_bytes(),
+            DEFAULT_GAS, 
+            to_yocto("0.01")
+        );
+        user.call(
+            ft_contract.account_id(), 
+            "ft_transfer_call", 
+            &json!({
+                "receiver_id": staking_contract.account_id(),
+                "amount": MAX_STAKE_PER_ACCOUNT,
+                "msg": ""
+            }).to_string().as_bytes(),
+            DEFAULT_GAS, 
+            1
+        ).assert_success();
+    }
+
+    // Alice boost with same amount as stake
+    alice.call(
+        boost_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": MAX_STAKE_PER_ACCOUNT,
+            "msg": "{\"action\":\"boost\"}"
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    let get_reward = |user: &UserAccount| -> u128 {
+        let account_json: AccountJson = root.view(
+            staking_contract.account_id(), 
+            "get_account_info", 
+            &json!({
+                "account_id": user.account_id()
+            }).to_string().as_bytes()
+        ).unwrap_json();
+        account_json.reward.0
+    };
+    let alice_reward_before = get_reward(&alice);
+    let bob_reward_before = get_reward(&bob);
+
+    // Produce some blocks
+    for _ in 0..5 {
+        alice.call(
+            staking_contract.account_id(), 
+            "owner_settle_global", 
+            &json!({}).to_string().as_bytes(),
+            DEFAULT_GAS, 
+            0
+        );
+    }
+
+    // Boosted alice accrue faster than bob with equal stake
+    let alice_accrued = get_reward(&alice) - alice_reward_before;
+    let bob_accrued = get_reward(&bob) - bob_reward_before;
+    assert!(bob_accrued > 0);
+    assert!(alice_accrued > bob_accrued);
+}
+
+#[test]
+fn deposit_zero_amount_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    );
+
+    let pool_info_before: PoolInfo = root.view(
+        staking_contract.account_id(), 
+        "get_pool_info", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": "0",
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+
+    // No stake and no new staker
+    let pool_info: PoolInfo = root.view(
+        staking_contract.account_id(), 
+        "get_pool_info", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(pool_info.total_stake_balance, pool_info_before.total_stake_balance);
+    assert_eq!(pool_info.total_stakers, pool_info_before.total_stakers);
+
+    let account_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(account_json.stake_balance.0, 0);
+}
+
+#[test]
+fn stake_harvest_unstake_withdraw_flow_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+    let stake_amount: u128 = 1_000_000_000_000_000_000_000_000_000;
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    let stake = |amount: u128| {
+        alice.call(
+            ft_contract.account_id(), 
+            "ft_transfer_call", 
+            &json!({
+                "receiver_id": staking_contract.account_id(),
+                "amount": U128(amount),
+                "msg": ""
+            }).to_string().as_bytes(),
+            DEFAULT_GAS, 
+            1
+        ).assert_success();
+    };
+
+    // Stake and harvest
+    stake(stake_amount);
+    produce_blocks(&root, 10);
+
+    let alice_balance_before = ft_balance_of(&root, &ft_contract, alice.account_id());
+    let contract_balance_before = ft_balance_of(&root, &ft_contract, staking_contract.account_id());
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let harvested_reward: u128 = outcome.unwrap_json::<TransferResult>().amount.0;
+    assert!(harvested_reward > 0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), alice_balance_before + harvested_reward);
+    assert_eq!(ft_balance_of(&root, &ft_contract, staking_contract.account_id()), contract_balance_before - harvested_reward);
+
+    // Stake more and unstake part
+    stake(stake_amount);
+    produce_blocks(&root, 10);
+    alice.call(
+        staking_contract.account_id(), 
+        "unstake", 
+        &json!({
+            "amount": U128(stake_amount / 2)
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    // Withdraw after unlock epoch
+    produce_epochs(&root, 2);
+    let alice_balance_before = ft_balance_of(&root, &ft_contract, alice.account_id());
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "withdraw", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let withdraw_result: TransferResult = outcome.unwrap_json();
+    assert_eq!(withdraw_result.kind, TransferKind::Withdraw);
+    assert_eq!(withdraw_result.amount.0, stake_amount / 2);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), alice_balance_before + stake_amount / 2);
+
+    // Account and pool aggregates are consistent
+    let account_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    let pool_info: PoolInfo = root.view(
+        staking_contract.account_id(), 
+        "get_pool_info", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+    let recount_stakers: u64 = root.view(
+        staking_contract.account_id(), 
+        "recount_stakers", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+
+    assert_eq!(account_json.stake_balance.0, stake_amount * 3 / 2);
+    assert_eq!(account_json.unstake_balance.0, 0);
+    assert_eq!(pool_info.total_stake_balance, account_json.stake_balance);
+    assert_eq!(pool_info.total_unstake_balance.0, 0);
+    assert_eq!(pool_info.total_stakers.0, recount_stakers as u128);
+    assert!(pool_info.total_reward.0 >= account_json.reward.0);
+    assert_eq!(
+        ft_balance_of(&root, &ft_contract, staking_contract.account_id()),
+        FT_STAKING_CONTRACT_BALANCE.parse::<u128>().unwrap() + stake_amount * 3 / 2 - harvested_reward
+    );
+}
+
+#[test]
+fn harvest_memo_test() {
+    let (_root, ft_contract, staking_contract, alice) = init();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "set_transfer_memos", 
+        &json!({
+            "harvest_memo": "pool-harvest",
+            "withdraw_memo": "pool-withdraw"
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    );
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+
+    // FT contract log memo of transfer
+    let logs: Vec<String> = outcome.promise_results()
+        .into_iter()
+        .flatten()
+        .flat_map(|result| result.logs().clone())
+        .collect();
+    assert!(logs.iter().any(|log| log == "Memo: pool-harvest"));
+}
+
+#[test]
+fn sync_reward_reserve_test() {
+    let (_root, ft_contract, staking_contract, alice) = init();
+
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "sync_reward_reserve", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    );
+    outcome.assert_success();
+    let reserve_before: U128 = outcome.unwrap_json();
+    assert_eq!(reserve_before.0, FT_STAKING_CONTRACT_BALANCE.parse::<u128>().unwrap());
+
+    // Direct transfer without fund_reward msg
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "sync_reward_reserve", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    );
+    outcome.assert_success();
+    let reserve_after: U128 = outcome.unwrap_json();
+    assert_eq!(reserve_after.0, reserve_before.0 + ALICE_DEPOSIT_BALANCE.parse::<u128>().unwrap());
+}
+
+
+#[test]
+fn withdraw_then_harvest_in_flight_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+    let stake_amount: u128 = 1_000_000_000_000_000_000_000_000_000;
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": U128(stake_amount),
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    produce_blocks(&root, 10);
+    alice.call(
+        staking_contract.account_id(), 
+        "unstake", 
+        &json!({
+            "amount": U128(stake_amount / 2)
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+    produce_epochs(&root, 2);
+
+    // Harvest run before withdraw callback, account is still locked
+    let outcome = alice.create_transaction(staking_contract.account_id())
+        .function_call("withdraw".to_string(), json!({}).to_string().into_bytes(), DEFAULT_GAS / 2, 1)
+        .function_call("harvest".to_string(), json!({}).to_string().into_bytes(), DEFAULT_GAS / 2, 1)
+        .submit();
+
+    if let ExecutionStatus::Failure(error) = &outcome.status() {
+        assert!(error.to_string().contains("ERR_OPERATION_IN_FLIGHT"));
+    } else {
+        unreachable!();
+    }
+
+    // Lock is released after callback, harvest work again
+    alice.call(
+        staking_contract.account_id(), 
+        "withdraw", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+    alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+}
+
+
+#[test]
+fn storage_unregister_force_with_reward_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+    let stake_amount: u128 = 1_000_000_000_000_000_000_000_000_000;
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": U128(stake_amount),
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    // Unstake and withdraw all, reward is left unharvested
+    produce_blocks(&root, 10);
+    alice.call(
+        staking_contract.account_id(), 
+        "unstake", 
+        &json!({
+            "amount": U128(stake_amount)
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+    produce_epochs(&root, 2);
+    alice.call(
+        staking_contract.account_id(), 
+        "withdraw", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    // Without force, account with residual reward can't unregister
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "storage_unregister", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    if let ExecutionStatus::Failure(error) = &outcome.status() {
+        assert!(error.to_string().contains("ERR_REWARD_NOT_HARVESTED"));
+    } else {
+        unreachable!();
+    }
+
+    // Force unregister pay reward before remove account
+    let alice_balance_before = ft_balance_of(&root, &ft_contract, alice.account_id());
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "storage_unregister", 
+        &json!({
+            "force": true
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let removed: bool = outcome.unwrap_json();
+    assert!(removed);
+    assert!(ft_balance_of(&root, &ft_contract, alice.account_id()) > alice_balance_before);
+
+    let storage_balance: U128 = root.view(
+        staking_contract.account_id(), 
+        "storage_balance_of", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(storage_balance.0, 0);
+}
+
+
+#[test]
+fn ft_transfer_call_invalid_msg_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    let alice_balance_before = ft_balance_of(&root, &ft_contract, alice.account_id());
+    let outcome = alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": "{\"action\":"
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+
+    // Malformed msg is refunded with reason in log
+    let logs: Vec<String> = outcome.promise_results()
+        .into_iter()
+        .flatten()
+        .flat_map(|result| result.logs().clone())
+        .collect();
+    assert!(logs.iter().any(|log| log.contains("ERR_INVALID_MSG")));
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), alice_balance_before);
+
+    let pool_info: PoolInfo = root.view(
+        staking_contract.account_id(), 
+        "get_pool_info", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(pool_info.total_stake_balance.0, 0);
+}
+
+
+#[test]
+fn harvest_fee_to_treasury_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+    let treasury = root.create_user("treasury".to_string(), to_yocto("10"));
+
+    root.call(
+        ft_contract.account_id(), 
+        "storage_deposit", 
+        &json!({
+            "account_id": treasury.account_id()
+        }).to_string().as_bytes(), 
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    // Owner set harvest fee 10% and treasury
+    let mut config = default_config();
+    config["harvest_fee_basis_points"] = json!(1000);
+    update_config(&staking_contract, &alice, config);
+    alice.call(
+        staking_contract.account_id(), 
+        "set_treasury_id", 
+        &json!({
+            "treasury_id": treasury.account_id()
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+    produce_blocks(&root, 10);
+
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+
+    // Fee land in treasury instead of reward reserve
+    let pool_info: PoolInfo = root.view(
+        staking_contract.account_id(), 
+        "get_pool_info", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+    assert!(pool_info.total_fee_collected.0 > 0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, treasury.account_id()), pool_info.total_fee_collected.0);
+}
+
+#[test]
+fn vesting_harvest_claim_test() {
+    let (root, ft_contract, staking_contract, alice) = init();
+
+    // Harvested reward vest over 100 blocks
+    let mut config = default_config();
+    config["vesting_blocks"] = json!(100);
+    update_config(&staking_contract, &alice, config);
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": ALICE_DEPOSIT_BALANCE,
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+    produce_blocks(&root, 10);
+
+    // Harvest move reward to vesting balance, nothing transferred
+    let balance_before_harvest: u128 = ft_balance_of(&root, &ft_contract, alice.account_id());
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let result: TransferResult = outcome.unwrap_json();
+    assert_eq!(result.kind, TransferKind::Vest);
+    let vesting_reward: u128 = result.amount.0;
+    assert!(vesting_reward > 0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), balance_before_harvest);
+
+    // Claim before full vest transfer released part only
+    produce_blocks(&root, 40);
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "claim_vested", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let partial_claim: TransferResult = outcome.unwrap_json();
+    assert_eq!(partial_claim.kind, TransferKind::ClaimVested);
+    assert!(partial_claim.amount.0 > 0);
+    assert!(partial_claim.amount.0 < vesting_reward);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), balance_before_harvest + partial_claim.amount.0);
+
+    // Claim after full vest transfer remainder
+    produce_blocks(&root, 100);
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "claim_vested", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let final_claim: TransferResult = outcome.unwrap_json();
+    assert_eq!(final_claim.amount.0, vesting_reward - partial_claim.amount.0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), balance_before_harvest + vesting_reward);
+}
+
+#[test]
+fn fee_on_transfer_token_stake_test() {
+    // Mock token burn 1% of every transfer
+    let mut config = default_config();
+    config["fee_on_transfer_token"] = json!(true);
+    let (root, ft_contract, staking_contract, alice) = init_mock_ft(100, config);
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    // Stake credit amount actually received
+    let balance_before_stake: u128 = ft_balance_of(&root, &ft_contract, alice.account_id());
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": "100000000000000000000",
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    let account_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(account_json.stake_balance, U128(99000000000000000000));
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), balance_before_stake - 100000000000000000000);
+
+    // Balance query fail, whole deposit is refunded and nothing staked
+    alice.call(
+        ft_contract.account_id(), 
+        "set_fail_balance_of", 
+        &json!({
+            "fail_balance_of": true
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": "100000000000000000000",
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+    alice.call(
+        ft_contract.account_id(), 
+        "set_fail_balance_of", 
+        &json!({
+            "fail_balance_of": false
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+
+    let account_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(account_json.stake_balance, U128(99000000000000000000));
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), balance_before_stake - 100000000000000000000);
+}
+
+#[test]
+fn unwrap_on_harvest_native_near_test() {
+    // Mock token act as wrap NEAR contract
+    let (root, ft_contract, staking_contract, alice) = init_mock_ft(0, default_config());
+    let bob = root.create_user("bob".to_string(), to_yocto("10"));
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": "1000000000000000000000000",
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "set_wrap_near_contract_id", 
+        &json!({
+            "wrap_near_contract_id": ft_contract.account_id()
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+    alice.call(
+        staking_contract.account_id(), 
+        "set_unwrap_on_harvest", 
+        &json!({
+            "unwrap_on_harvest": true
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+
+    produce_blocks(&root, 100);
+
+    // Reward is unwrapped and bob receive native NEAR, no token
+    let alice_token_before: u128 = ft_balance_of(&root, &ft_contract, alice.account_id());
+    let contract_token_before: u128 = ft_balance_of(&root, &ft_contract, staking_contract.account_id());
+    let bob_near_before: u128 = bob.account().unwrap().amount;
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest_to", 
+        &json!({
+            "receiver_id": bob.account_id()
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+
+    let harvested_reward: u128 = outcome.unwrap_json::<TransferResult>().amount.0;
+    assert!(harvested_reward > 0);
+    assert_eq!(bob.account().unwrap().amount, bob_near_before + harvested_reward);
+    assert_eq!(ft_balance_of(&root, &ft_contract, bob.account_id()), 0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), alice_token_before);
+    assert_eq!(ft_balance_of(&root, &ft_contract, staking_contract.account_id()), contract_token_before - harvested_reward);
+}
+
+pub fn set_fail_transfers(ft_contract: &UserAccount, owner: &UserAccount, fail_transfers: bool) {
+    owner.call(
+        ft_contract.account_id(), 
+        "set_fail_transfers", 
+        &json!({
+            "fail_transfers": fail_transfers
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        0
+    ).assert_success();
+}
+
+#[test]
+fn harvest_transfer_failed_test() {
+    let (root, ft_contract, staking_contract, alice) = init_mock_ft(0, default_config());
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": "1000000000000000000000000",
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    produce_blocks(&root, 10);
+
+    // Transfer fail, reward is kept and nothing is paid
+    set_fail_transfers(&ft_contract, &alice, true);
+    let alice_balance_before: u128 = ft_balance_of(&root, &ft_contract, alice.account_id());
+    let contract_balance_before: u128 = ft_balance_of(&root, &ft_contract, staking_contract.account_id());
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    assert_eq!(outcome.unwrap_json::<TransferResult>().amount.0, 0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), alice_balance_before);
+
+    let account_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert!(account_json.reward.0 > 0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, staking_contract.account_id()), contract_balance_before);
+
+    // Lock is released, harvest again pay whole kept reward
+    set_fail_transfers(&ft_contract, &alice, false);
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "harvest", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let harvested_reward: u128 = outcome.unwrap_json::<TransferResult>().amount.0;
+    assert!(harvested_reward >= account_json.reward.0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), alice_balance_before + harvested_reward);
+}
+
+#[test]
+fn withdraw_transfer_failed_test() {
+    let (root, ft_contract, staking_contract, alice) = init_mock_ft(0, default_config());
+    let stake_amount: u128 = 1_000_000_000_000_000_000_000_000;
+
+    alice.call(
+        staking_contract.account_id(), 
+        "storage_deposit", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        ft_contract.account_id(), 
+        "ft_transfer_call", 
+        &json!({
+            "receiver_id": staking_contract.account_id(),
+            "amount": U128(stake_amount),
+            "msg": ""
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+
+    alice.call(
+        staking_contract.account_id(), 
+        "unstake", 
+        &json!({
+            "amount": U128(stake_amount)
+        }).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    ).assert_success();
+    produce_epochs(&root, 2);
+
+    // Transfer fail, unstake balance is restored and nothing is sent
+    set_fail_transfers(&ft_contract, &alice, true);
+    let alice_balance_before: u128 = ft_balance_of(&root, &ft_contract, alice.account_id());
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "withdraw", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    let withdraw_result: TransferResult = outcome.unwrap_json();
+    assert_eq!(withdraw_result.kind, TransferKind::Withdraw);
+    assert_eq!(withdraw_result.amount.0, 0);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), alice_balance_before);
+
+    let account_json: AccountJson = root.view(
+        staking_contract.account_id(), 
+        "get_account_info", 
+        &json!({
+            "account_id": alice.account_id()
+        }).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(account_json.unstake_balance.0, stake_amount);
+    assert!(account_json.can_withdraw);
+    let pool_info: PoolInfo = root.view(
+        staking_contract.account_id(), 
+        "get_pool_info", 
+        &json!({}).to_string().as_bytes()
+    ).unwrap_json();
+    assert_eq!(pool_info.total_unstake_balance.0, stake_amount);
+
+    // Lock is released, withdraw again send whole unstake balance
+    set_fail_transfers(&ft_contract, &alice, false);
+    let outcome = alice.call(
+        staking_contract.account_id(), 
+        "withdraw", 
+        &json!({}).to_string().as_bytes(),
+        DEFAULT_GAS, 
+        1
+    );
+    outcome.assert_success();
+    assert_eq!(outcome.unwrap_json::<TransferResult>().amount.0, stake_amount);
+    assert_eq!(ft_balance_of(&root, &ft_contract, alice.account_id()), alice_balance_before + stake_amount);
+}
@@ -0,0 +1,162 @@
+/**
+ * Mock fungible token for simulation tests
+ * Charge fee_basis_points of every transfer and can be set to fail ft_balance_of or ft_transfer
+ * near_withdraw burn token and send same amount of native NEAR, as wrap NEAR contract
+ */
+use near_sdk::collections::LookupMap;
+use near_sdk::{near_bindgen, AccountId, env, PanicOnDefault, Balance, Gas, Promise, PromiseResult, PromiseOrValue, ext_contract};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128};
+
+const BASIS_POINTS: u32 = 10_000;
+const FT_ON_TRANSFER_GAS: Gas = 35_000_000_000_000;
+const FT_RESOLVE_TRANSFER_GAS: Gas = 10_000_000_000_000;
+
+#[ext_contract(ext_ft_receiver)]
+pub trait FungibleTokenReceiver {
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
+}
+
+#[ext_contract(ext_self)]
+pub trait ExtMockFt {
+    fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> U128;
+}
+
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+#[near_bindgen]
+pub struct MockFt {
+    pub owner_id: AccountId,
+    pub balances: LookupMap<AccountId, Balance>,
+    pub fee_basis_points: u32, // Fee burned from every transfer, receiver get amount minus fee
+    pub fail_balance_of: bool,
+    pub fail_transfers: bool // ft_transfer panic, ft_transfer_call is not affected
+}
+
+#[near_bindgen]
+impl MockFt {
+
+    #[init]
+    pub fn new(owner_id: AccountId, total_supply: U128, fee_basis_points: u32) -> Self {
+        let mut balances: LookupMap<AccountId, Balance> = LookupMap::new(b"b".to_vec());
+        balances.insert(&owner_id, &total_supply.0);
+
+        MockFt {
+            owner_id,
+            balances,
+            fee_basis_points,
+            fail_balance_of: false,
+            fail_transfers: false
+        }
+    }
+
+    pub fn set_fail_balance_of(&mut self, fail_balance_of: bool) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "ERR_ONLY_OWNER");
+        self.fail_balance_of = fail_balance_of;
+    }
+
+    pub fn set_fail_transfers(&mut self, fail_transfers: bool) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "ERR_ONLY_OWNER");
+        self.fail_transfers = fail_transfers;
+    }
+
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<AccountId>) {
+        let account_id: AccountId = account_id.unwrap_or_else(env::predecessor_account_id);
+        if self.balances.get(&account_id).is_none() {
+            self.balances.insert(&account_id, &0);
+        }
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<U128> {
+        self.balances.get(&account_id).map(|_| U128(1))
+    }
+
+    pub fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        assert!(!self.fail_balance_of, "ERR_BALANCE_OF_FAILED");
+        U128(self.balances.get(&account_id).unwrap_or(0))
+    }
+
+    #[payable]
+    pub fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        let _ = memo;
+        assert!(!self.fail_transfers, "ERR_TRANSFER_FAILED");
+        self.internal_transfer(&env::predecessor_account_id(), &receiver_id, amount.0);
+    }
+
+    /**
+     * Burn token of caller and transfer same amount of native NEAR, caller must attach 1 yoctoNEAR
+     */
+    #[payable]
+    pub fn near_withdraw(&mut self, amount: U128) -> Promise {
+        assert_eq!(env::attached_deposit(), 1, "ERR_REQUIRE_ONE_YOCTO");
+        let account_id: AccountId = env::predecessor_account_id();
+        let balance: Balance = self.balances.get(&account_id).expect("ERR_NOT_REGISTERED");
+        assert!(amount.0 <= balance, "ERR_NOT_ENOUGH_BALANCE");
+        self.balances.insert(&account_id, &(balance - amount.0));
+
+        Promise::new(account_id).transfer(amount.0)
+    }
+
+    /**
+     * Receiver is called with full amount, it only received amount minus fee
+     */
+    #[payable]
+    pub fn ft_transfer_call(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>, msg: String) -> Promise {
+        let _ = memo;
+        let sender_id: AccountId = env::predecessor_account_id();
+        self.internal_transfer(&sender_id, &receiver_id, amount.0);
+
+        ext_ft_receiver::ft_on_transfer(
+            sender_id.clone(),
+            amount,
+            msg,
+            &receiver_id,
+            0,
+            FT_ON_TRANSFER_GAS
+        ).then(
+            ext_self::ft_resolve_transfer(
+                sender_id,
+                receiver_id.clone(),
+                amount,
+                &env::current_account_id(),
+                0,
+                FT_RESOLVE_TRANSFER_GAS
+            )
+        )
+    }
+
+    /**
+     * Refund unused amount without fee, capped by receiver balance, return used amount
+     */
+    #[private]
+    pub fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> U128 {
+        let unused_amount: Balance = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U128>(&value)
+                .map(|unused| std::cmp::min(unused.0, amount.0))
+                .unwrap_or(amount.0),
+            _ => amount.0
+        };
+
+        let receiver_balance: Balance = self.balances.get(&receiver_id).unwrap_or(0);
+        let refund: Balance = std::cmp::min(unused_amount, receiver_balance);
+        if refund > 0 {
+            self.balances.insert(&receiver_id, &(receiver_balance - refund));
+            let sender_balance: Balance = self.balances.get(&sender_id).unwrap_or(0);
+            self.balances.insert(&sender_id, &(sender_balance + refund));
+        }
+
+        U128(amount.0 - refund)
+    }
+}
+
+impl MockFt {
+    pub(crate) fn internal_transfer(&mut self, sender_id: &AccountId, receiver_id: &AccountId, amount: Balance) {
+        let sender_balance: Balance = self.balances.get(sender_id).expect("ERR_NOT_REGISTERED");
+        assert!(amount <= sender_balance, "ERR_NOT_ENOUGH_BALANCE");
+        let receiver_balance: Balance = self.balances.get(receiver_id).expect("ERR_NOT_REGISTERED");
+
+        let fee: Balance = amount * Balance::from(self.fee_basis_points) / Balance::from(BASIS_POINTS);
+        self.balances.insert(sender_id, &(sender_balance - amount));
+        self.balances.insert(receiver_id, &(receiver_balance + amount - fee));
+    }
+}